@@ -7,11 +7,11 @@ use std::num::Wrapping;
 
 extern crate abr;
 use abr::abr::ABR;
+use abr::btree::BTree;
 use abr::node::{BoxedNode, Node};
 
 extern crate rayon_adaptive;
 use rayon_adaptive::prelude::*;
-use rayon_adaptive::Policy;
 
 use rand::seq::SliceRandom;
 use rand::Rng;
@@ -21,37 +21,30 @@ fn sum_iter(tree: &ABR<u64, ()>) -> Wrapping<u64> {
 }
 
 fn sum_par(tree: &ABR<u64, ()>) -> Wrapping<u64> {
-    tree.par_iter().map(|n| Wrapping(n.key)).sum()
+    tree.adaptive_par_iter().map(|n| Wrapping(n.key)).sum()
 }
 
 fn sum_par_level(tree: &ABR<u64, ()>, level: usize) -> Wrapping<u64> {
-    tree.par_iter().levels(level).map(|n| Wrapping(n.key)).sum()
-}
-
-fn find_depth_first(tree: &ABR<u64, ()>, depth: usize, target: u64) -> Option<&BoxedNode<u64, ()>> {
-    tree.par_iter()
-        .cut()
-        .depth_first(depth)
-        .with_policy(Policy::Join(1))
-        .map(|i_par| {
-            i_par
-                .levels(5)
-                .iterator_fold(|mut i_seq| i_seq.find(|e| e.key == target))
-                .reduce(|| None, |a, b| a.or(b))
-                .map(|n| Err(n))
-                .unwrap_or(Ok(()))
-        })
-        .try_reduce(|| (), |_, _| Ok(()))
-        .err()
+    tree.adaptive_par_iter()
+        .levels(level)
+        .map(|n| Wrapping(n.key))
+        .sum()
 }
 
 fn find_normal(tree: &ABR<u64, ()>, target: u64) -> Option<&BoxedNode<u64, ()>> {
-    tree.par_iter()
+    tree.adaptive_par_iter()
         .levels(5)
         .iterator_fold(|mut i_seq| i_seq.find(|e| e.key == target))
         .reduce(|| None, |a, b| a.or(b))
 }
 
+fn sum_par_btree(tree: &BTree<u64, ()>, level: usize) -> Wrapping<u64> {
+    tree.par_iter()
+        .levels(level)
+        .map(|(k, _)| Wrapping(*k))
+        .sum()
+}
+
 fn criterion_benchmark_par_vs_iter(c: &mut Criterion) {
     let sizes = vec![1_000, 10_000, 50_000, 100_000];
 
@@ -98,42 +91,58 @@ fn criterion_benchmark_levels(c: &mut Criterion) {
     );
 }
 
-fn criterion_benchmark_depth_first(c: &mut Criterion) {
-    let depths: Vec<usize> = (3..7).collect();
-
-    let size = 20_000;
+fn criterion_benchmark_find(c: &mut Criterion) {
+    let sizes = vec![10_000, 20_000, 50_000];
 
     c.bench(
-        "Depth first optimisation for find()",
+        "Cancelled vs plain parallel find()",
         ParameterizedBenchmark::new(
-            "With depth_first",
-            move |b: &mut Bencher, depth: &usize| {
+            "par_find_any",
+            |b, size| {
                 b.iter_with_setup(
                     || {
-                        let mut vec = (0..size as u64).collect::<Vec<u64>>();
+                        let mut vec = (0..*size as u64).collect::<Vec<u64>>();
 
                         vec.shuffle(&mut rand::thread_rng());
                         vec.into_iter().collect()
                     },
                     |tree: ABR<u64, _>| {
-                        let random = rand::thread_rng().gen_range(0, size as u64);
-                        assert_eq!(find_depth_first(&tree, *depth, random).unwrap().key, random);
+                        let random = rand::thread_rng().gen_range(0, *size as u64);
+                        assert_eq!(tree.par_find_any(|n| n.key == random).unwrap().key, random);
                         tree
                     },
                 )
             },
-            depths,
+            sizes,
         )
-        .with_function("Without", move |b, _| {
+        .with_function("par_find_first", |b, size| {
             b.iter_with_setup(
                 || {
-                    let mut vec = (0..size as u64).collect::<Vec<u64>>();
+                    let mut vec = (0..*size as u64).collect::<Vec<u64>>();
 
                     vec.shuffle(&mut rand::thread_rng());
                     vec.into_iter().collect()
                 },
                 |tree: ABR<u64, _>| {
-                    let random = rand::thread_rng().gen_range(0, size as u64);
+                    let random = rand::thread_rng().gen_range(0, *size as u64);
+                    assert_eq!(
+                        tree.par_find_first(|n| n.key == random).unwrap().key,
+                        random
+                    );
+                    tree
+                },
+            )
+        })
+        .with_function("Without", |b, size| {
+            b.iter_with_setup(
+                || {
+                    let mut vec = (0..*size as u64).collect::<Vec<u64>>();
+
+                    vec.shuffle(&mut rand::thread_rng());
+                    vec.into_iter().collect()
+                },
+                |tree: ABR<u64, _>| {
+                    let random = rand::thread_rng().gen_range(0, *size as u64);
                     assert_eq!(find_normal(&tree, random).unwrap().key, random);
                     tree
                 },
@@ -142,10 +151,77 @@ fn criterion_benchmark_depth_first(c: &mut Criterion) {
     );
 }
 
+fn criterion_benchmark_btree_levels(c: &mut Criterion) {
+    let capacities = vec![2, 5, 11, 31, 63];
+
+    c.bench(
+        "Binary vs wide nodes, parallel sum",
+        ParameterizedBenchmark::new(
+            "B-tree",
+            |b, capacity| {
+                b.iter_with_setup(
+                    || {
+                        let mut tree = BTree::with_capacity(*capacity);
+                        for key in repeat_with(rand::random).take(100_000) {
+                            tree.insert(key, ());
+                        }
+                        tree
+                    },
+                    |tree: BTree<u64, _>| {
+                        sum_par_btree(&tree, 10);
+                        tree
+                    },
+                )
+            },
+            capacities,
+        )
+        .with_function("BST", |b, _| {
+            b.iter_with_setup(
+                || repeat_with(rand::random).take(100_000).collect(),
+                |tree: ABR<u64, _>| {
+                    sum_par_level(&tree, 10);
+                    tree
+                },
+            )
+        }),
+    );
+}
+
+fn criterion_benchmark_btree_find(c: &mut Criterion) {
+    let capacities = vec![2, 5, 11, 31, 63];
+    let size = 20_000;
+
+    c.bench_function_over_inputs(
+        "Parallel find() on B-trees",
+        move |b: &mut Bencher, capacity: &usize| {
+            b.iter_with_setup(
+                || {
+                    let mut vec = (0..size as u64).collect::<Vec<u64>>();
+                    vec.shuffle(&mut rand::thread_rng());
+
+                    let mut tree = BTree::with_capacity(*capacity);
+                    for key in vec {
+                        tree.insert(key, ());
+                    }
+                    tree
+                },
+                |tree: BTree<u64, _>| {
+                    let random = rand::thread_rng().gen_range(0, size as u64);
+                    assert_eq!(tree.par_find_any(|k, _| *k == random), Some((&random, &())));
+                    tree
+                },
+            )
+        },
+        capacities,
+    );
+}
+
 criterion_group!(
     benches,
     //    criterion_benchmark_par_vs_iter,
     //    criterion_benchmark_levels
-    criterion_benchmark_depth_first
+    criterion_benchmark_find,
+    criterion_benchmark_btree_levels,
+    criterion_benchmark_btree_find
 );
 criterion_main!(benches);
@@ -0,0 +1,62 @@
+//! Compares the parallel iteration of a boxed tree and of an arena tree
+//! holding the same keys.
+use abr::abr::ABR;
+use abr::arena::ArenaABR;
+use abr::bencher::Bencher;
+use rayon_adaptive::prelude::*;
+use std::io::Error;
+use std::iter::repeat_with;
+use std::num::Wrapping;
+use std::path::Path;
+
+#[cfg(feature = "logs")]
+extern crate rayon_logs as rayon;
+use rayon::ThreadPoolBuilder;
+
+fn random_keys(size: usize) -> Vec<u64> {
+    repeat_with(rand::random).take(size).collect()
+}
+
+fn sum_boxed(tree: ABR<u64, ()>, _level: Option<usize>) -> ABR<u64, ()> {
+    let pool = ThreadPoolBuilder::new()
+        .build()
+        .expect("pool creation failed");
+    pool.install(|| {
+        tree.adaptive_par_iter()
+            .map(|n| Wrapping(n.key))
+            .reduce(|| Wrapping(0), |a, b| a + b)
+    });
+    tree
+}
+
+fn sum_arena(tree: ArenaABR<u64, ()>, _level: Option<usize>) -> ArenaABR<u64, ()> {
+    let pool = ThreadPoolBuilder::new()
+        .build()
+        .expect("pool creation failed");
+    pool.install(|| {
+        tree.par_iter()
+            .map(|n| Wrapping(n.key))
+            .reduce(|| Wrapping(0), |a, b| a + b)
+    });
+    tree
+}
+
+fn main() -> Result<(), Error> {
+    let sizes = vec![100_000, 1_000_000, 5_000_000];
+
+    Bencher::new(
+        Path::new("bench_results/boxed"),
+        |size| random_keys(size).into_iter().collect::<ABR<_, _>>(),
+        sum_boxed,
+    )
+    .run_benchmark("boxed nodes", sizes.clone(), vec![None])?;
+
+    Bencher::new(
+        Path::new("bench_results/arena"),
+        |size| random_keys(size).into_iter().collect::<ArenaABR<_, _>>(),
+        sum_arena,
+    )
+    .run_benchmark("arena nodes", sizes, vec![None])?;
+
+    Ok(())
+}
@@ -51,7 +51,7 @@ fn main() {
 
     let start = precise_time_ns();
     let sum_par: Wrapping<u64> = pool.install(|| {
-        tree.par_iter()
+        tree.adaptive_par_iter()
             .map(|n| Wrapping(*n.key))
             .reduce(|| Wrapping(0), |a, b| a + b)
     });
@@ -0,0 +1,60 @@
+//! Compares the search latency of a boxed tree and of the same tree
+//! frozen in van Emde Boas order.
+use abr::abr::ABR;
+use abr::bencher::Bencher;
+use abr::veb::VebABR;
+use std::io::Error;
+use std::iter::repeat_with;
+use std::path::Path;
+
+const SIZE: usize = 10_000_000;
+const QUERIES: usize = 1_000_000;
+
+fn search_boxed<'a>(
+    (tree, queries): (&'a ABR<u64, ()>, &'a [u64]),
+    _level: Option<usize>,
+) -> (&'a ABR<u64, ()>, &'a [u64]) {
+    let found = queries.iter().filter(|q| tree.contains_key(*q)).count();
+    assert!(found >= queries.len() / 2);
+    (tree, queries)
+}
+
+fn search_veb<'a>(
+    (tree, queries): (&'a VebABR<u64, ()>, &'a [u64]),
+    _level: Option<usize>,
+) -> (&'a VebABR<u64, ()>, &'a [u64]) {
+    let found = queries.iter().filter(|q| tree.contains_key(*q)).count();
+    assert!(found >= queries.len() / 2);
+    (tree, queries)
+}
+
+fn main() -> Result<(), Error> {
+    let keys: Vec<u64> = repeat_with(rand::random).take(SIZE).collect();
+    // half of the queries hit, half of them (almost surely) miss
+    let queries: Vec<u64> = keys
+        .iter()
+        .take(QUERIES / 2)
+        .cloned()
+        .chain(repeat_with(rand::random).take(QUERIES / 2))
+        .collect();
+
+    let boxed: ABR<u64, ()> = keys.iter().cloned().collect();
+    let veb = keys.iter().cloned().collect::<ABR<u64, ()>>().freeze_veb();
+
+    // the trees are built once, only the searches are measured
+    Bencher::new(
+        Path::new("bench_results/search_boxed"),
+        |_| (&boxed, queries.as_slice()),
+        search_boxed,
+    )
+    .run_benchmark("boxed nodes", vec![SIZE], vec![None])?;
+
+    Bencher::new(
+        Path::new("bench_results/search_veb"),
+        |_| (&veb, queries.as_slice()),
+        search_veb,
+    )
+    .run_benchmark("van Emde Boas layout", vec![SIZE], vec![None])?;
+
+    Ok(())
+}
@@ -1,19 +1,79 @@
-use crate::abr_iterator::ABRIterator;
+use crate::abr_iterator::{ABRIterator, ABRRangeIterator};
+#[cfg(feature = "rayon_adaptive")]
 use crate::abr_parallel_iterator::ABRParallelIterator;
-use crate::node::Node;
+use crate::augment::Augment;
+use crate::cursor::{Cursor, CursorMut};
+use crate::frozen::FrozenABR;
 use crate::node::OptBoxedNode;
+use crate::node::{BoxedNode, Node};
+#[cfg(feature = "rayon_adaptive")]
+use crate::traversal::DepthFirstParallelIterator;
+use crate::traversal::{DepthFirstIterator, LevelOrderIterator, Order};
+use crate::veb::VebABR;
+#[cfg(feature = "rayon_adaptive")]
+use rayon_adaptive::prelude::*;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
 use std::fmt::Display;
+use std::ops::RangeBounds;
 
 /// A binary search tree with a key/value system
 ///
 /// A binary tree, where each node has between 0 and 2 children,
 /// and whose length is known (the number of nodes in the tree).
+///
+/// Each node also keeps a summary of its subtree for the augmentation `A`
+/// (see [Augment]{trait.Augment.html}). By default, nothing is maintained.
 #[derive(Debug)]
-pub struct ABR<K, V> {
-    pub root: OptBoxedNode<K, V>,
+pub struct ABR<K, V, A = ()>
+where
+    A: Augment<K, V>,
+{
+    pub root: OptBoxedNode<K, V, A>,
     pub length: usize,
 }
 
+/// Creates an empty tree, whatever its augmentation.
+///
+/// # Examples
+/// Basic usage :
+///
+/// ```
+/// use abr::abr::ABR;
+/// use abr::augment::Sum;
+///
+/// let mut btree: ABR<_, _, Sum> = ABR::default();
+/// btree.insert("one", 1);
+/// btree.insert("two", 2);
+/// assert_eq!(btree.summary(), 3);
+/// ```
+impl<K, V, A> Default for ABR<K, V, A>
+where
+    K: Ord,
+    A: Augment<K, V>,
+{
+    fn default() -> Self {
+        ABR {
+            root: None,
+            length: 0,
+        }
+    }
+}
+
+impl<K, V, A> Clone for ABR<K, V, A>
+where
+    K: Clone,
+    V: Clone,
+    A: Augment<K, V>,
+{
+    fn clone(&self) -> Self {
+        ABR {
+            root: self.root.clone(),
+            length: self.length,
+        }
+    }
+}
+
 /// Enables collection into a tree
 ///
 /// From any collection of pairs of any type and `()`, collect it
@@ -26,15 +86,16 @@ pub struct ABR<K, V> {
 ///
 /// let mut btree : ABR<_, _> = (1..10).collect();
 /// ```
-impl<K> std::iter::FromIterator<K> for ABR<K, ()>
+impl<K, A> std::iter::FromIterator<K> for ABR<K, (), A>
 where
     K: Ord,
+    A: Augment<K, ()>,
 {
     fn from_iter<T>(iter: T) -> Self
     where
         T: IntoIterator<Item = K>,
     {
-        let mut a = ABR::new();
+        let mut a = ABR::default();
         for key in iter {
             a.insert(key, ());
         }
@@ -59,12 +120,46 @@ where
     /// btree.insert(1, "Hello");
     /// ```
     pub fn new() -> ABR<K, V> {
-        ABR {
-            root: None,
-            length: 0,
+        ABR::default()
+    }
+
+    /// Returns a mutable reference to the value associated to a key.
+    ///
+    /// If the key is present in the tree, the function will return
+    /// `Some(value)`, where `value` is the associated value to the key.
+    /// If not, the function will return `None`.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let mut btree = ABR::new();
+    ///
+    /// btree.insert(1, "hello");
+    /// *btree.get_mut(&1).unwrap() = "world";
+    ///
+    /// assert_eq!(btree.get(&1), Some(&"world"));
+    /// ```
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if let Some(root) = &mut self.root {
+            root.get_mut(key)
+        } else {
+            None
         }
     }
+}
 
+impl<K, V, A> ABR<K, V, A>
+where
+    K: Ord,
+    A: Augment<K, V>,
+{
     /// Inserts a key and value pair in the tree
     ///
     /// # Examples
@@ -95,6 +190,10 @@ where
 
     /// Returns `true` if the specified key is contained in the binary tree.
     ///
+    /// The key may be any borrowed form of the tree's key type, but
+    /// the ordering on the borrowed form must match the ordering on
+    /// the key type.
+    ///
     /// # Examples
     /// Basic usage :
     ///
@@ -106,11 +205,15 @@ where
     /// btree.insert(1, "hello");
     /// btree.insert(2, "world");
     ///
-    /// assert!(btree.contains_key(1));
-    /// assert!(!btree.contains_key(42));
+    /// assert!(btree.contains_key(&1));
+    /// assert!(!btree.contains_key(&42));
     /// ```
-    pub fn contains_key(&self, key: K) -> bool {
-        self.root.as_ref().and_then(|o| o.get(&key)).is_some()
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.root.as_ref().and_then(|o| o.get(key)).is_some()
     }
 
     /// Returns the value associated to a key.
@@ -130,17 +233,66 @@ where
     /// btree.insert(1, "hello");
     /// btree.insert(2, "world");
     ///
-    /// assert_eq!(*btree.get(1).unwrap(), "hello");
-    /// assert!(btree.get(42).is_none());
+    /// assert_eq!(*btree.get(&1).unwrap(), "hello");
+    /// assert!(btree.get(&42).is_none());
+    /// ```
+    ///
+    /// Keys can be looked up by any borrowed form, avoiding allocations:
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let mut btree = ABR::new();
+    /// btree.insert(String::from("hello"), 1);
+    ///
+    /// assert_eq!(btree.get("hello"), Some(&1));
     /// ```
-    pub fn get(&self, key: K) -> Option<&V> {
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
         if let Some(root) = &self.root {
-            root.get(&key)
+            root.get(key)
         } else {
             None
         }
     }
 
+    /// Applies a function to the value associated to a key.
+    ///
+    /// Unlike `get_mut`, this keeps the summaries of an augmented tree
+    /// up to date. Returns `true` if the key was found.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    /// use abr::augment::Sum;
+    ///
+    /// let mut btree: ABR<_, _, Sum> = ABR::default();
+    ///
+    /// btree.insert(1, 10);
+    /// btree.insert(2, 20);
+    ///
+    /// assert!(btree.modify(&1, |v| *v += 5));
+    /// assert!(!btree.modify(&42, |v| *v += 5));
+    /// assert_eq!(btree.aggregate(..), 35);
+    /// ```
+    pub fn modify<Q, F>(&mut self, key: &Q, f: F) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        F: FnOnce(&mut V),
+    {
+        if let Some(root) = &mut self.root {
+            root.modify(key, f)
+        } else {
+            false
+        }
+    }
+
     /// Returns `true` if the tree is empty.
     ///
     /// # Examples
@@ -159,16 +311,32 @@ where
         self.length == 0
     }
 
+    /// Builds a balanced tree from key and value pairs sorted by
+    /// strictly increasing keys.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<_, _> = ABR::from_sorted((1..=7).map(|k| (k, k * 10)).collect());
+    /// assert_eq!(tree.root.as_ref().map(|n| n.key), Some(4));
+    /// assert_eq!(tree.get(&6), Some(&60));
+    /// ```
+    pub fn from_sorted(entries: Vec<(K, V)>) -> ABR<K, V, A> {
+        let length = entries.len();
+        ABR {
+            root: Node::from_sorted(&mut entries.into_iter(), length),
+            length,
+        }
+    }
+
     /// Removes a node from the tree
     ///
     /// Tries to remove a node from the tree, given its key.
     /// If the key is found, it will return the removed value in
-    /// a `Some(value)`. If the key doesn't exist, the function
-    /// currently panics.
-    ///
-    /// # Panics
-    /// The function will panic if the key is not present in the
-    /// tree.
+    /// a `Some(value)`. If the key doesn't exist, `None` is returned.
     ///
     /// # Examples
     /// Basic usage :
@@ -179,44 +347,155 @@ where
     /// let mut btree : ABR<_, _> = (1..10).collect();
     ///
     /// assert_eq!(btree.remove(&7), Some(()));
+    /// assert_eq!(btree.remove(&42), None);
     /// ```
-    pub fn remove(&mut self, key: &K) -> Option<V> {
-        let child_ref = Node::get_node(&mut self.root, key);
-        let found_value = ABR::remove_node(child_ref);
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let found_value = Node::remove(&mut self.root, key);
         if found_value.is_some() {
             self.length -= 1;
         }
         found_value
     }
 
-    fn remove_node(child_ref: &mut OptBoxedNode<K, V>) -> Option<V> {
-        child_ref.take().map(|mut to_remove| {
-            if !to_remove.is_leaf() {
-                if to_remove.children[0].is_none() {
-                    *child_ref = to_remove.children[1].take();
-                    to_remove.value
-                } else if to_remove.children[1].is_none() {
-                    *child_ref = to_remove.children[0].take();
-                    to_remove.value
-                } else {
-                    let min_node_ref = Node::get_min(&mut to_remove.children[1]);
-                    std::mem::swap(&mut min_node_ref.as_mut().unwrap().key, &mut to_remove.key);
+    /// Returns the summary of the whole tree.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    /// use abr::augment::Count;
+    ///
+    /// let mut btree: ABR<_, _, Count> = ABR::default();
+    /// assert_eq!(btree.summary(), 0);
+    ///
+    /// btree.insert("hello", "world");
+    /// assert_eq!(btree.summary(), 1);
+    /// ```
+    pub fn summary(&self) -> A::Summary {
+        self.root
+            .as_ref()
+            .map(|root| root.summary.clone())
+            .unwrap_or_else(A::identity)
+    }
+
+    /// Combines the summaries of all the entries whose keys lie in
+    /// the given range, in order.
+    ///
+    /// This runs in a time proportional to the height of the tree.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    /// use abr::augment::{Max, Sum};
+    ///
+    /// let mut sums: ABR<_, _, Sum> = ABR::default();
+    /// let mut maxs: ABR<_, _, Max> = ABR::default();
+    /// for (key, value) in vec![(5, 50), (3, 30), (7, 70), (1, 10), (4, 40)] {
+    ///     sums.insert(key, value);
+    ///     maxs.insert(key, value);
+    /// }
+    ///
+    /// assert_eq!(sums.aggregate(3..=5), 120);
+    /// assert_eq!(sums.aggregate(..), 200);
+    /// assert_eq!(maxs.aggregate(..5), Some(40));
+    /// assert_eq!(maxs.aggregate(8..), None);
+    /// ```
+    pub fn aggregate<Q, R>(&self, range: R) -> A::Summary
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        Node::aggregate(&self.root, &range, false, false)
+    }
+
+    /// Returns the node holding the `index`-th smallest key, if any.
+    ///
+    /// Indices start at 0. This runs in a time proportional to the height
+    /// of the tree.
+    ///
+    /// # Examples
+    /// Basic usage:
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<_, _> = vec![50, 30, 70, 10, 40].into_iter().collect();
+    /// assert_eq!(tree.nth(0).unwrap().key, 10);
+    /// assert_eq!(tree.nth(3).unwrap().key, 50);
+    /// assert!(tree.nth(5).is_none());
+    /// ```
+    pub fn nth(&self, index: usize) -> Option<&BoxedNode<K, V, A>> {
+        let mut index = index;
+        let mut current = self.root.as_ref();
 
-                    let mut min_node_value = ABR::remove_node(min_node_ref).unwrap();
-                    std::mem::swap(&mut min_node_value, &mut to_remove.value);
+        while let Some(node) = current {
+            let left_size = Node::subtree_size(&node.children[0]);
+            current = match index.cmp(&left_size) {
+                Ordering::Equal => return Some(node),
+                Ordering::Less => node.children[0].as_ref(),
+                Ordering::Greater => {
+                    index -= left_size + 1;
+                    node.children[1].as_ref()
+                }
+            };
+        }
+        None
+    }
 
-                    *child_ref = Some(to_remove); // reconnect child_ref to its son since we remove another node
+    /// Returns the number of keys in the tree strictly smaller than
+    /// the given key.
+    ///
+    /// The key does not need to be present in the tree. This runs in
+    /// a time proportional to the height of the tree.
+    ///
+    /// # Examples
+    /// Basic usage:
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<_, _> = vec![50, 30, 70, 10, 40].into_iter().collect();
+    /// assert_eq!(tree.rank(&10), 0);
+    /// assert_eq!(tree.rank(&50), 3);
+    /// assert_eq!(tree.rank(&45), 3);
+    /// assert_eq!(tree.rank(&100), 5);
+    /// ```
+    pub fn rank<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut rank = 0;
+        let mut current = self.root.as_ref();
 
-                    min_node_value
+        while let Some(node) = current {
+            let left_size = Node::subtree_size(&node.children[0]);
+            current = match key.cmp(node.key.borrow()) {
+                Ordering::Equal => return rank + left_size,
+                Ordering::Less => node.children[0].as_ref(),
+                Ordering::Greater => {
+                    rank += left_size + 1;
+                    node.children[1].as_ref()
                 }
-            } else {
-                to_remove.value
-            }
-        })
+            };
+        }
+        rank
     }
 
     /// Returns an iterator from the tree
     ///
+    /// The iterator allocates a stack as deep as the tree. Nodes do not
+    /// know their parent : allocation-free iteration is offered by
+    /// `ArenaABR` instead.
+    ///
     /// # Examples
     /// Basic usage:
     ///
@@ -226,27 +505,315 @@ where
     /// let tree: ABR<_, _> = vec![5, 3, 7, 1, 4, 2, 6].into_iter().collect();
     /// assert!(tree.iter().map(|n| n.key).eq(1..=7));
     /// ```
-    pub fn iter<'a>(&'a self) -> ABRIterator<'a, K, V> {
+    pub fn iter<'a>(&'a self) -> ABRIterator<'a, K, V, A> {
         ABRIterator::new(self)
     }
 
+    /// Returns an iterator over the nodes whose keys lie in the given range,
+    /// in order.
+    ///
+    /// # Examples
+    /// Basic usage:
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<_, _> = vec![5, 3, 7, 1, 4, 2, 6].into_iter().collect();
+    /// assert!(tree.range(3..6).map(|n| n.key).eq(3..6));
+    /// assert!(tree.range(..=2).map(|n| n.key).eq(1..=2));
+    /// ```
+    pub fn range<'a, Q, R>(&'a self, range: R) -> ABRRangeIterator<'a, K, V, Q, R, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        ABRRangeIterator::new(self, range)
+    }
+
+    /// Returns a pre-order iterator over the nodes of the tree, along with
+    /// their depth.
+    ///
+    /// # Examples
+    /// Basic usage:
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<_, _> = vec![2, 1, 3].into_iter().collect();
+    /// assert!(tree.iter_preorder().map(|(n, d)| (n.key, d)).eq(vec![(2, 0), (1, 1), (3, 1)]));
+    /// ```
+    pub fn iter_preorder(&self) -> DepthFirstIterator<'_, K, V, A> {
+        DepthFirstIterator::new(self, Order::Pre)
+    }
+
+    /// Returns a post-order iterator over the nodes of the tree, along with
+    /// their depth : children always come before their parent.
+    ///
+    /// # Examples
+    /// Basic usage:
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<_, _> = vec![2, 1, 3].into_iter().collect();
+    /// assert!(tree.iter_postorder().map(|(n, d)| (n.key, d)).eq(vec![(1, 1), (3, 1), (2, 0)]));
+    /// ```
+    pub fn iter_postorder(&self) -> DepthFirstIterator<'_, K, V, A> {
+        DepthFirstIterator::new(self, Order::Post)
+    }
+
+    /// Returns a level-order iterator over the nodes of the tree, along
+    /// with their depth.
+    ///
+    /// There is no parallel version : each level mixes nodes of all the
+    /// subtrees, which cannot be handed to different tasks.
+    ///
+    /// # Examples
+    /// Basic usage:
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<_, _> = vec![2, 1, 3, 4].into_iter().collect();
+    /// assert!(tree.iter_level_order().map(|(n, _)| n.key).eq(vec![2, 1, 3, 4]));
+    /// ```
+    pub fn iter_level_order(&self) -> LevelOrderIterator<'_, K, V, A> {
+        LevelOrderIterator::new(self)
+    }
+
+    /// Get a parallel (using rayon_adaptive) pre-order iterator from the
+    /// tree, yielding the nodes along with their depth.
+    /// ```
+    /// use abr::abr::ABR;
+    /// use rayon_adaptive::prelude::*;
+    ///
+    /// let tree: ABR<_, _> = vec![4, 2, 6, 1, 3, 5, 7].into_iter().collect();
+    /// assert_eq!(tree.par_iter_preorder().map(|(_, d)| d).reduce(|| 0, |a, b| a + b), 10);
+    /// ```
+    #[cfg(feature = "rayon_adaptive")]
+    pub fn par_iter_preorder(&self) -> DepthFirstParallelIterator<'_, K, V, A> {
+        DepthFirstParallelIterator::new(self, Order::Pre)
+    }
+
+    /// Get a parallel (using rayon_adaptive) post-order iterator from the
+    /// tree, yielding the nodes along with their depth.
+    ///
+    /// The nodes are handed to the tasks by whole subtrees, which can be
+    /// processed independently.
+    /// ```
+    /// use abr::abr::ABR;
+    /// use rayon_adaptive::prelude::*;
+    ///
+    /// let tree: ABR<_, _> = vec![4, 2, 6, 1, 3, 5, 7].into_iter().collect();
+    /// let leaves = tree
+    ///     .par_iter_postorder()
+    ///     .map(|(n, _)| n.is_leaf() as usize)
+    ///     .reduce(|| 0, |a, b| a + b);
+    /// assert_eq!(leaves, 4);
+    /// ```
+    #[cfg(feature = "rayon_adaptive")]
+    pub fn par_iter_postorder(&self) -> DepthFirstParallelIterator<'_, K, V, A> {
+        DepthFirstParallelIterator::new(self, Order::Post)
+    }
+
+    /// Returns a cursor on the first node whose key is not less than `key`,
+    /// or on the ghost position if there is none.
+    ///
+    /// # Examples
+    /// Basic usage:
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<_, _> = vec![5, 3, 7, 1, 4, 2, 6].into_iter().collect();
+    /// let mut cursor = tree.cursor(&4);
+    /// cursor.move_next();
+    /// assert_eq!(cursor.key(), Some(&5));
+    /// ```
+    pub fn cursor<Q>(&self, key: &Q) -> Cursor<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Cursor::new(self, key)
+    }
+
+    /// Returns a cursor allowing to modify the tree, on the first node whose
+    /// key is not less than `key`, or on the ghost position if there is none.
+    ///
+    /// # Examples
+    /// Basic usage:
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let mut tree: ABR<_, _> = vec![1, 2, 4].into_iter().collect();
+    /// tree.cursor_mut(&4).insert_before(3, ());
+    /// assert!(tree.iter().map(|n| n.key).eq(1..=4));
+    /// ```
+    pub fn cursor_mut<Q>(&mut self, key: &Q) -> CursorMut<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        CursorMut::new(self, key)
+    }
+
     /// Get a parallel iterator (using rayon_adaptive) from the tree,
     /// allowing parallel operations like sum or fold.
+    ///
+    /// A stock rayon iterator is obtained with `par_iter` (or
+    /// `into_par_iter` on a reference to the tree) with the `rayon`
+    /// feature.
     /// ```
     /// use abr::abr::ABR;
     /// use rayon_adaptive::prelude::*;
     ///
     /// let tree: ABR<_, _> = vec![5, 3, 7, 1, 4, 2, 6].into_iter().collect();
-    /// assert_eq!(tree.par_iter().map(|n| n.key).reduce(|| 0, |a, b| a + b), 28);
+    /// assert_eq!(tree.adaptive_par_iter().map(|n| n.key).reduce(|| 0, |a, b| a + b), 28);
     /// ```
-    pub fn par_iter<'a>(&'a self) -> ABRParallelIterator<'a, K, V> {
+    #[cfg(feature = "rayon_adaptive")]
+    pub fn adaptive_par_iter<'a>(&'a self) -> ABRParallelIterator<'a, K, V, A> {
         ABRParallelIterator::new(self)
     }
+
+    /// Builds a tree from a parallel iterator (using rayon_adaptive) over
+    /// key and value pairs.
+    ///
+    /// Each task sorts the entries it gets into a run, the runs are merged
+    /// and the tree is built balanced from the result. As with `insert`,
+    /// the last value of a key wins.
+    /// ```
+    /// use abr::abr::ABR;
+    /// use rayon_adaptive::prelude::*;
+    ///
+    /// let keys: ABR<u32, ()> = (0..100).collect();
+    /// let tree: ABR<u32, u32> = ABR::from_adaptive_par_iter(keys.adaptive_par_iter().map(|n| (n.key % 10, n.key)));
+    /// assert_eq!(tree.length, 10);
+    /// assert_eq!(tree.get(&3), Some(&93));
+    /// ```
+    #[cfg(feature = "rayon_adaptive")]
+    pub fn from_adaptive_par_iter<I>(iterator: I) -> ABR<K, V, A>
+    where
+        I: ParallelIterator<Item = (K, V)>,
+        K: Send,
+        V: Send,
+    {
+        let entries = iterator
+            .iterator_fold(|entries| sorted_run(entries.collect()))
+            .reduce(Vec::new, merge_runs);
+        ABR::from_sorted(entries)
+    }
+
+    /// Consumes the tree, turning it into an immutable tree stored in
+    /// Eytzinger order, for fast lookups.
+    ///
+    /// # Examples
+    /// Basic usage:
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<_, _> = vec![5, 3, 7, 1, 4, 2, 6].into_iter().collect();
+    /// let frozen = tree.freeze();
+    /// assert!(frozen.contains_key(&4));
+    /// assert!(frozen.iter().map(|(k, _)| *k).eq(1..=7));
+    /// ```
+    pub fn freeze(self) -> FrozenABR<K, V> {
+        FrozenABR::from_sorted(self.into_sorted_vec())
+    }
+
+    /// Consumes the tree, turning it into an immutable tree stored in
+    /// van Emde Boas order, for fast lookups on huge trees.
+    ///
+    /// # Examples
+    /// Basic usage:
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<_, _> = vec![5, 3, 7, 1, 4, 2, 6].into_iter().collect();
+    /// let frozen = tree.freeze_veb();
+    /// assert!(frozen.contains_key(&4));
+    /// assert!(frozen.iter().map(|(k, _)| *k).eq(1..=7));
+    /// ```
+    pub fn freeze_veb(self) -> VebABR<K, V> {
+        VebABR::from_sorted(self.into_sorted_vec())
+    }
+
+    /// Consumes the tree, returning its entries in order.
+    fn into_sorted_vec(self) -> Vec<(K, V)> {
+        let mut entries = Vec::with_capacity(self.length);
+        let mut stack: Vec<BoxedNode<K, V, A>> = Vec::new();
+        let mut current = self.root;
+        loop {
+            while let Some(mut node) = current {
+                current = node.children[0].take();
+                stack.push(node);
+            }
+            match stack.pop() {
+                Some(node) => {
+                    let Node {
+                        key,
+                        value,
+                        children: [_, right],
+                        ..
+                    } = *node;
+                    entries.push((key, value));
+                    current = right;
+                }
+                None => break,
+            }
+        }
+        entries
+    }
 }
 
-impl<K, V> ABR<K, V>
+/// Sorts entries by key into a run, keeping the last value of each key.
+#[cfg(any(feature = "rayon", feature = "rayon_adaptive"))]
+pub(crate) fn sorted_run<K: Ord, V>(mut entries: Vec<(K, V)>) -> Vec<(K, V)> {
+    // the sort is stable : the last value of a key stays the last one
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut run: Vec<(K, V)> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        match run.last_mut() {
+            Some(last) if last.0 == entry.0 => *last = entry,
+            _ => run.push(entry),
+        }
+    }
+    run
+}
+
+/// Merges two runs, the values of `right` winning on equal keys.
+#[cfg(any(feature = "rayon", feature = "rayon_adaptive"))]
+pub(crate) fn merge_runs<K: Ord, V>(left: Vec<(K, V)>, right: Vec<(K, V)>) -> Vec<(K, V)> {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    loop {
+        let ordering = match (left.peek(), right.peek()) {
+            (Some(l), Some(r)) => l.0.cmp(&r.0),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+        match ordering {
+            Ordering::Less => merged.push(left.next().unwrap()),
+            Ordering::Greater => merged.push(right.next().unwrap()),
+            Ordering::Equal => {
+                left.next();
+                merged.push(right.next().unwrap());
+            }
+        }
+    }
+    merged
+}
+
+impl<K, V, A> ABR<K, V, A>
 where
     K: Ord + Display,
+    A: Augment<K, V>,
 {
     /// Converts the tree into a dot graphviz file and converts it
     /// to a .png file.
@@ -267,6 +834,7 @@ where
 #[cfg(test)]
 mod abr_tests {
     use super::*;
+    use std::ops::Bound;
 
     #[test]
     fn new() {
@@ -334,4 +902,125 @@ mod abr_tests {
         a.insert("Four", 4);
         assert!(a.get("Five").is_none());
     }
+
+    #[test]
+    fn get_borrowed() {
+        let mut a = ABR::new();
+        a.insert(String::from("Two"), 2);
+        a.insert(String::from("Three"), 3);
+        assert_eq!(*a.get("Three").unwrap(), 3);
+        assert!(a.contains_key("Two"));
+        assert!(!a.contains_key("Four"));
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut a = ABR::new();
+        a.insert("Two", 2);
+        a.insert("Three", 3);
+        *a.get_mut("Two").unwrap() = 42;
+        assert_eq!(*a.get("Two").unwrap(), 42);
+        assert!(a.get_mut("Four").is_none());
+    }
+
+    #[test]
+    fn remove_borrowed() {
+        let mut a = ABR::new();
+        a.insert(String::from("Two"), 2);
+        a.insert(String::from("Three"), 3);
+        assert_eq!(a.remove("Two"), Some(2));
+        assert_eq!(a.length, 1);
+        assert!(!a.contains_key("Two"));
+    }
+
+    #[test]
+    fn range() {
+        let a: ABR<_, _> = vec![5, 3, 7, 1, 4, 2, 6, 9, 8].into_iter().collect();
+        assert!(a.range(3..7).map(|n| n.key).eq(3..7));
+        assert!(a.range(4..).map(|n| n.key).eq(4..=9));
+        assert!(a.range(..).map(|n| n.key).eq(1..=9));
+        assert!(a.range(10..).next().is_none());
+    }
+
+    #[test]
+    fn range_borrowed() {
+        let mut a = ABR::new();
+        for word in &["apple", "banana", "cherry", "date"] {
+            a.insert(word.to_string(), ());
+        }
+        let keys: Vec<&str> = a
+            .range::<str, _>((Bound::Included("b"), Bound::Excluded("d")))
+            .map(|n| n.key.as_str())
+            .collect();
+        assert_eq!(keys, vec!["banana", "cherry"]);
+    }
+
+    #[test]
+    fn remove_missing() {
+        let mut a: ABR<_, _> = (1..10).collect();
+        assert!(a.remove(&42).is_none());
+        assert_eq!(a.length, 9);
+    }
+
+    #[test]
+    fn remove_two_children() {
+        let mut a: ABR<_, _> = vec![5, 3, 8, 1, 4, 7, 9, 6].into_iter().collect();
+        assert_eq!(a.remove(&5), Some(()));
+        assert_eq!(a.remove(&3), Some(()));
+        assert!(a.iter().map(|n| n.key).eq(vec![1, 4, 6, 7, 8, 9]));
+    }
+
+    #[test]
+    fn aggregate_after_updates() {
+        use crate::augment::Sum;
+
+        let mut a: ABR<u64, u64, Sum> = ABR::default();
+        let mut x: u64 = 42;
+        for _ in 0..500 {
+            x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            let key = (x >> 33) % 200;
+            if x & 0b11 == 0 {
+                a.remove(&key);
+            } else {
+                a.insert(key, key * 2);
+            }
+        }
+
+        for &(start, end) in &[(0, 200), (13, 57), (100, 101), (150, 120), (199, 200)] {
+            let expected: u64 = a
+                .iter()
+                .filter(|n| n.key >= start && n.key < end)
+                .map(|n| n.value)
+                .sum();
+            assert_eq!(a.aggregate(start..end), expected);
+        }
+        assert!(a.modify(&a.root.as_ref().unwrap().key.clone(), |v| *v += 1));
+        assert_eq!(a.aggregate(..), a.iter().map(|n| n.value).sum::<u64>());
+    }
+
+    #[test]
+    fn sizes_after_updates() {
+        let mut a: ABR<_, _> = vec![5, 3, 8, 1, 4, 7, 9, 6].into_iter().collect();
+        a.remove(&5);
+        a.remove(&1);
+        a.insert(2, ());
+        assert_eq!(a.root.as_ref().unwrap().size, a.length);
+        assert!(a.iter().all(|n| n.size
+            == 1 + Node::subtree_size(&n.children[0]) + Node::subtree_size(&n.children[1])));
+    }
+
+    #[test]
+    fn nth_and_rank() {
+        let keys = vec![13, 4, 27, 8, 1, 19, 42, 3, 16, 25];
+        let a: ABR<_, _> = keys.iter().cloned().collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        for (index, key) in sorted.iter().enumerate() {
+            assert_eq!(a.nth(index).unwrap().key, *key);
+            assert_eq!(a.rank(key), index);
+            assert_eq!(a.rank(&(key + 1)), index + 1);
+        }
+        assert!(a.nth(keys.len()).is_none());
+        assert_eq!(a.rank(&0), 0);
+    }
 }
@@ -1,24 +1,52 @@
 use crate::abr::ABR;
-use crate::node::BoxedNode;
+use crate::augment::Augment;
+use crate::node::{BoxedNode, Node};
+use std::borrow::Borrow;
 use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
 use std::vec::IntoIter;
 
-pub type RefNode<'a, K, V> = &'a BoxedNode<K, V>;
-pub type OptRefNode<'a, K, V> = Option<RefNode<'a, K, V>>;
+pub type RefNode<'a, K, V, A = ()> = &'a BoxedNode<K, V, A>;
+pub type OptRefNode<'a, K, V, A = ()> = Option<RefNode<'a, K, V, A>>;
 
 /// A sequential iterator for the [ABR]{struct.ABR.html} structure.
 ///
 /// This iterator goes through the tree in order, providing an ordered
 /// list of elements from the tree.
+///
+/// At most `remaining` more nodes are yielded, which is exactly the
+/// number of nodes left unless the iterator was cut short, and `position`
+/// is the rank in the tree of the next node.
 #[derive(Debug)]
-pub struct ABRIterator<'a, K, V> {
-    pub small_nodes: IntoIter<RefNode<'a, K, V>>,
-    pub big_nodes: VecDeque<RefNode<'a, K, V>>,
+pub struct ABRIterator<'a, K, V, A = ()>
+where
+    A: Augment<K, V>,
+{
+    pub small_nodes: IntoIter<RefNode<'a, K, V, A>>,
+    pub big_nodes: VecDeque<RefNode<'a, K, V, A>>,
+    pub remaining: usize,
+    pub position: usize,
+}
+
+impl<'a, K, V, A> Clone for ABRIterator<'a, K, V, A>
+where
+    A: Augment<K, V>,
+{
+    fn clone(&self) -> Self {
+        ABRIterator {
+            small_nodes: self.small_nodes.clone(),
+            big_nodes: self.big_nodes.clone(),
+            remaining: self.remaining,
+            position: self.position,
+        }
+    }
 }
 
-impl<'a, K, V> ABRIterator<'a, K, V>
+impl<'a, K, V, A> ABRIterator<'a, K, V, A>
 where
     K: Ord,
+    A: Augment<K, V>,
 {
     /// Create a new iterator from a tree
     ///
@@ -29,63 +57,310 @@ where
     /// let tree : ABR<_, _> = vec![5, 3, 7, 1, 4, 2, 6].into_iter().collect();
     /// assert!(tree.iter().map(|n| n.key).eq(1..=7));
     /// ```
-    pub fn new(tree: &'a ABR<K, V>) -> ABRIterator<'a, K, V> {
-        let mut smalls: Vec<RefNode<'a, K, V>> = Vec::new();
-        let mut bigs: VecDeque<RefNode<'a, K, V>> = VecDeque::new();
+    pub fn new(tree: &'a ABR<K, V, A>) -> ABRIterator<'a, K, V, A> {
+        let mut smalls: Vec<RefNode<'a, K, V, A>> = Vec::new();
+        let mut bigs: VecDeque<RefNode<'a, K, V, A>> = VecDeque::new();
 
-        ABRIterator::descent(&mut smalls, &mut bigs, &tree.root.as_ref().unwrap());
+        if let Some(root) = &tree.root {
+            ABRIterator::descent(&mut smalls, &mut bigs, root);
+        }
 
         ABRIterator {
             small_nodes: smalls.into_iter(),
             big_nodes: bigs,
+            remaining: Node::subtree_size(&tree.root),
+            position: 0,
+        }
+    }
+
+    /// Create a new iterator from a tree, starting at the first node
+    /// whose key lies after the given bound.
+    pub fn starting_at<Q>(tree: &'a ABR<K, V, A>, start: Bound<&Q>) -> ABRIterator<'a, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut bigs: VecDeque<RefNode<'a, K, V, A>> = VecDeque::new();
+        let mut remaining = 0;
+        let mut current = tree.root.as_ref();
+
+        while let Some(node) = current {
+            let after_start = match start {
+                Bound::Included(key) => node.key.borrow() >= key,
+                Bound::Excluded(key) => node.key.borrow() > key,
+                Bound::Unbounded => true,
+            };
+            if after_start {
+                bigs.push_front(node);
+                remaining += 1 + Node::subtree_size(&node.children[1]);
+                current = node.children[0].as_ref();
+            } else {
+                current = node.children[1].as_ref();
+            }
+        }
+
+        ABRIterator {
+            small_nodes: Vec::new().into_iter(),
+            big_nodes: bigs,
+            remaining,
+            position: Node::subtree_size(&tree.root) - remaining,
         }
     }
 
+    /// Skips the next `count` nodes, using the sizes of the subtrees to
+    /// jump over whole subtrees at once.
+    ///
+    /// # Examples
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree : ABR<_, _> = (0..100).collect();
+    /// let mut iterator = tree.iter();
+    /// iterator.advance(42);
+    /// assert_eq!(iterator.len(), 58);
+    /// assert!(iterator.map(|n| n.key).eq(42..100));
+    /// ```
+    pub fn advance(&mut self, count: usize) {
+        let count = count.min(self.remaining);
+        self.remaining -= count;
+        self.position += count;
+        skip_front(&mut self.small_nodes, &mut self.big_nodes, count);
+    }
+
+    /// Splits off the first `index` nodes into a new iterator, this one
+    /// keeping the nodes after them.
+    ///
+    /// # Examples
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree : ABR<_, _> = (0..100).collect();
+    /// let mut back = tree.iter();
+    /// let front = back.split_off_front(30);
+    /// assert_eq!((front.position, back.position), (0, 30));
+    /// assert!(front.map(|n| n.key).eq(0..30));
+    /// assert!(back.map(|n| n.key).eq(30..100));
+    /// ```
+    pub fn split_off_front(&mut self, index: usize) -> ABRIterator<'a, K, V, A> {
+        let index = index.min(self.remaining);
+        let mut front = self.clone();
+        front.remaining = index;
+        self.advance(index);
+        front
+    }
+
     pub fn descent(
-        smalls: &mut Vec<RefNode<'a, K, V>>,
-        bigs: &mut VecDeque<RefNode<'a, K, V>>,
-        mut start: RefNode<'a, K, V>,
+        smalls: &mut Vec<RefNode<'a, K, V, A>>,
+        bigs: &mut VecDeque<RefNode<'a, K, V, A>>,
+        start: RefNode<'a, K, V, A>,
     ) {
-        loop {
-            if start.nb_children() == 0 {
+        push_left_path(smalls, bigs, start);
+    }
+}
+
+impl<'a, K, V, A> Iterator for ABRIterator<'a, K, V, A>
+where
+    K: Ord,
+    A: Augment<K, V>,
+{
+    type Item = &'a BoxedNode<K, V, A>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        self.position += 1;
+        pop_next(&mut self.small_nodes, &mut self.big_nodes)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V, A> ExactSizeIterator for ABRIterator<'a, K, V, A>
+where
+    K: Ord,
+    A: Augment<K, V>,
+{
+}
+
+/// A node of a binary tree, seen through the pointer its parent holds,
+/// which knows the size of its subtree.
+///
+/// This is all the in-order stack of an iterator needs, so that the
+/// same walk serves every tree whose nodes are reached this way.
+pub(crate) trait SizedNode: Sized {
+    fn children(&self) -> &[Option<Self>; 2];
+    fn size(&self) -> usize;
+}
+
+impl<K, V, A> SizedNode for BoxedNode<K, V, A>
+where
+    A: Augment<K, V>,
+{
+    fn children(&self) -> &[Option<Self>; 2] {
+        &self.children
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+fn subtree_size<P: SizedNode>(node: &Option<P>) -> usize {
+    node.as_ref().map_or(0, P::size)
+}
+
+/// Pushes the leftmost path of a subtree : the nodes having a left child
+/// come after it, from `bigs`, the others right away, from `smalls`.
+pub(crate) fn push_left_path<'a, P: SizedNode>(
+    smalls: &mut Vec<&'a P>,
+    bigs: &mut VecDeque<&'a P>,
+    mut start: &'a P,
+) {
+    loop {
+        match start.children() {
+            [None, None] => {
                 smalls.push(start);
                 break;
             }
-            if start.children[0].is_some() {
+            [Some(left), _] => {
                 bigs.push_front(start);
-                start = start.children[0].as_ref().unwrap();
-            } else {
+                start = left;
+            }
+            [None, Some(right)] => {
                 smalls.push(start);
-                start = start.children[1].as_ref().unwrap();
+                start = right;
             }
         }
     }
 }
 
-impl<'a, K, V> Iterator for ABRIterator<'a, K, V>
-where
-    K: Ord,
-{
-    type Item = &'a BoxedNode<K, V>;
+/// Pops the next node of an in-order stack, pushing the leftmost path
+/// of its right subtree if it has a left child.
+pub(crate) fn pop_next<'a, P: SizedNode>(
+    smalls: &mut IntoIter<&'a P>,
+    bigs: &mut VecDeque<&'a P>,
+) -> Option<&'a P> {
+    smalls.next().or_else(|| {
+        let node = bigs.pop_front()?;
+        if let Some(right) = &node.children()[1] {
+            let mut new_smalls = Vec::new();
+            push_left_path(&mut new_smalls, bigs, right);
+            *smalls = new_smalls.into_iter();
+        }
+        Some(node)
+    })
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.small_nodes.next().or_else(|| {
-            let node = self.big_nodes.pop_front();
-
-            if let Some(n) = node {
-                if n.children[1].is_some() {
-                    let mut new_smalls = Vec::new();
-                    ABRIterator::descent(
-                        &mut new_smalls,
-                        &mut self.big_nodes,
-                        n.children[1].as_ref().unwrap(),
-                    );
-                    self.small_nodes = new_smalls.into_iter();
+/// Skips the next `count` nodes of an in-order stack holding at least
+/// as many, using the sizes of the subtrees to jump over whole subtrees
+/// at once.
+pub(crate) fn skip_front<'a, P: SizedNode>(
+    smalls: &mut IntoIter<&'a P>,
+    bigs: &mut VecDeque<&'a P>,
+    mut count: usize,
+) {
+    let skipped = count.min(smalls.len());
+    if skipped > 0 {
+        smalls.nth(skipped - 1);
+        count -= skipped;
+    }
+
+    while count > 0 {
+        let node = bigs.pop_front().unwrap();
+        let size = 1 + subtree_size(&node.children()[1]);
+        if count >= size {
+            count -= size;
+            continue;
+        }
+        // the next node lies in the right subtree, at position count - 1
+        let mut position = count - 1;
+        let mut current = node.children()[1].as_ref().unwrap();
+        loop {
+            let left = subtree_size(&current.children()[0]);
+            if position <= left {
+                bigs.push_front(current);
+                if position == left {
+                    break;
                 }
-                Some(n)
+                current = current.children()[0].as_ref().unwrap();
             } else {
-                None
+                position -= left + 1;
+                current = current.children()[1].as_ref().unwrap();
             }
-        })
+        }
+        break;
+    }
+}
+
+/// A sequential iterator over the nodes of an [ABR]{struct.ABR.html}
+/// whose keys lie in a given range.
+///
+/// Nodes are yielded in order, starting from the first key after the
+/// start bound, and stopping at the end bound.
+#[derive(Debug)]
+pub struct ABRRangeIterator<'a, K, V, Q: ?Sized, R, A = ()>
+where
+    A: Augment<K, V>,
+{
+    pub iterator: ABRIterator<'a, K, V, A>,
+    pub range: R,
+    marker: PhantomData<fn(&Q)>,
+}
+
+impl<'a, K, V, Q, R, A> ABRRangeIterator<'a, K, V, Q, R, A>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+    A: Augment<K, V>,
+{
+    /// Create a new range iterator from a tree
+    ///
+    /// # Examples
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree : ABR<_, _> = vec![5, 3, 7, 1, 4, 2, 6].into_iter().collect();
+    /// assert!(tree.range(2..=4).map(|n| n.key).eq(2..=4));
+    /// ```
+    pub fn new(tree: &'a ABR<K, V, A>, range: R) -> ABRRangeIterator<'a, K, V, Q, R, A> {
+        ABRRangeIterator {
+            iterator: ABRIterator::starting_at(tree, range.start_bound()),
+            range,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V, Q, R, A> Iterator for ABRRangeIterator<'a, K, V, Q, R, A>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+    A: Augment<K, V>,
+{
+    type Item = &'a BoxedNode<K, V, A>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.iterator.next()?;
+        let before_end = match self.range.end_bound() {
+            Bound::Included(key) => node.key.borrow() <= key,
+            Bound::Excluded(key) => node.key.borrow() < key,
+            Bound::Unbounded => true,
+        };
+
+        if before_end {
+            Some(node)
+        } else {
+            // keys only grow from here, no need to explore the rest
+            self.iterator.small_nodes = Vec::new().into_iter();
+            self.iterator.big_nodes.clear();
+            self.iterator.remaining = 0;
+            None
+        }
     }
 }
@@ -0,0 +1,308 @@
+use crate::abr::ABR;
+use crate::abr_iterator::ABRIterator;
+use std::borrow::Borrow;
+use std::collections::vec_deque;
+use std::collections::VecDeque;
+use std::iter::Flatten;
+use std::option;
+
+/// An iterator over all the values associated to a key in an
+/// [ABRMulti]{struct.ABRMulti.html}, in insertion order.
+pub type MultiValues<'a, V> = Flatten<option::IntoIter<&'a VecDeque<V>>>;
+
+/// A binary search tree where a key can be associated to several values
+///
+/// Unlike [ABR]{struct.ABR.html}, inserting a key that is already present
+/// does not replace its value : every value is kept, in insertion order.
+#[derive(Debug)]
+pub struct ABRMulti<K, V> {
+    pub tree: ABR<K, VecDeque<V>>,
+    pub length: usize,
+}
+
+impl<K, V> Default for ABRMulti<K, V>
+where
+    K: Ord,
+{
+    fn default() -> Self {
+        ABRMulti::new()
+    }
+}
+
+impl<K, V> ABRMulti<K, V>
+where
+    K: Ord,
+{
+    /// Create a new, empty multimap.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr_multi::ABRMulti;
+    /// let mut multi = ABRMulti::new();
+    ///
+    /// // You can insert things now.
+    /// multi.insert(1, "Hello");
+    /// ```
+    pub fn new() -> ABRMulti<K, V> {
+        ABRMulti {
+            tree: ABR::new(),
+            length: 0,
+        }
+    }
+
+    /// Inserts a key and value pair in the multimap
+    ///
+    /// If the key is already present, the value is added after
+    /// the ones already associated to it.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr_multi::ABRMulti;
+    ///
+    /// let mut multi = ABRMulti::new();
+    ///
+    /// multi.insert("hello", "world");
+    /// multi.insert("hello", "there");
+    /// assert_eq!(multi.length, 2);
+    /// ```
+    pub fn insert(&mut self, key: K, value: V) {
+        if let Some(values) = self.tree.get_mut(&key) {
+            values.push_back(value);
+        } else {
+            let mut values = VecDeque::new();
+            values.push_back(value);
+            self.tree.insert(key, values);
+        }
+        self.length += 1;
+    }
+
+    /// Returns `true` if at least one value is associated to the key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.contains_key(key)
+    }
+
+    /// Returns `true` if the multimap is empty.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns an iterator over all the values associated to a key,
+    /// in insertion order.
+    ///
+    /// If the key is not present, the iterator is empty.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr_multi::ABRMulti;
+    ///
+    /// let mut multi = ABRMulti::new();
+    ///
+    /// multi.insert(1, "hello");
+    /// multi.insert(2, "big");
+    /// multi.insert(1, "world");
+    ///
+    /// assert!(multi.get_all(&1).eq(&["hello", "world"]));
+    /// assert_eq!(multi.get_all(&42).count(), 0);
+    /// ```
+    pub fn get_all<Q>(&self, key: &Q) -> MultiValues<'_, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.get(key).into_iter().flatten()
+    }
+
+    /// Removes the oldest value associated to a key.
+    ///
+    /// If the key is found, it will return the removed value in
+    /// a `Some(value)`. The key is removed from the tree with its
+    /// last value.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr_multi::ABRMulti;
+    ///
+    /// let mut multi = ABRMulti::new();
+    ///
+    /// multi.insert(1, "hello");
+    /// multi.insert(1, "world");
+    ///
+    /// assert_eq!(multi.remove_one(&1), Some("hello"));
+    /// assert_eq!(multi.remove_one(&1), Some("world"));
+    /// assert_eq!(multi.remove_one(&1), None);
+    /// ```
+    pub fn remove_one<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let values = self.tree.get_mut(key)?;
+        let value = values.pop_front();
+        if values.is_empty() {
+            self.tree.remove(key);
+        }
+        self.length -= 1;
+        value
+    }
+
+    /// Removes a key and all its associated values.
+    ///
+    /// The removed values are returned in insertion order. If the
+    /// key is not present, nothing is returned.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr_multi::ABRMulti;
+    ///
+    /// let mut multi = ABRMulti::new();
+    ///
+    /// multi.insert(1, "hello");
+    /// multi.insert(1, "world");
+    ///
+    /// assert_eq!(multi.remove_all(&1), vec!["hello", "world"]);
+    /// assert!(multi.is_empty());
+    /// ```
+    pub fn remove_all<Q>(&mut self, key: &Q) -> Vec<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if !self.tree.contains_key(key) {
+            return Vec::new();
+        }
+        let values = self.tree.remove(key).unwrap_or_default();
+        self.length -= values.len();
+        Vec::from(values)
+    }
+
+    /// Returns an iterator over the multimap
+    ///
+    /// Keys are visited in order, and values sharing the same key are
+    /// yielded together, in insertion order.
+    ///
+    /// # Examples
+    /// Basic usage:
+    ///
+    /// ```
+    /// use abr::abr_multi::ABRMulti;
+    ///
+    /// let mut multi = ABRMulti::new();
+    /// multi.insert(2, 'b');
+    /// multi.insert(1, 'a');
+    /// multi.insert(2, 'c');
+    ///
+    /// assert!(multi.iter().eq(vec![(&1, &'a'), (&2, &'b'), (&2, &'c')]));
+    /// ```
+    pub fn iter<'a>(&'a self) -> ABRMultiIterator<'a, K, V> {
+        ABRMultiIterator {
+            nodes: self.tree.iter(),
+            current: None,
+        }
+    }
+}
+
+/// A sequential iterator for the [ABRMulti]{struct.ABRMulti.html} structure.
+///
+/// This iterator goes through the keys in order, yielding each value
+/// along with its key.
+#[derive(Debug)]
+pub struct ABRMultiIterator<'a, K, V> {
+    nodes: ABRIterator<'a, K, VecDeque<V>>,
+    current: Option<(&'a K, vec_deque::Iter<'a, V>)>,
+}
+
+impl<'a, K, V> Iterator for ABRMultiIterator<'a, K, V>
+where
+    K: Ord,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((key, values)) = &mut self.current {
+                if let Some(value) = values.next() {
+                    return Some((key, value));
+                }
+            }
+            let node = self.nodes.next()?;
+            self.current = Some((&node.key, node.value.iter()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod abr_multi_tests {
+    use super::*;
+
+    #[test]
+    fn insert_duplicates() {
+        let mut m = ABRMulti::new();
+        m.insert("Two", 2);
+        m.insert("Three", 3);
+        m.insert("Two", 22);
+        assert_eq!(m.length, 3);
+        assert_eq!(m.tree.length, 2);
+        assert!(m.get_all("Two").eq(&[2, 22]));
+    }
+
+    #[test]
+    fn remove_one_keeps_order() {
+        let mut m = ABRMulti::new();
+        m.insert(1, 'a');
+        m.insert(1, 'b');
+        m.insert(1, 'c');
+        assert_eq!(m.remove_one(&1), Some('a'));
+        assert!(m.get_all(&1).eq(&['b', 'c']));
+        assert_eq!(m.length, 2);
+    }
+
+    #[test]
+    fn remove_one_last_value_removes_key() {
+        let mut m = ABRMulti::new();
+        m.insert(1, 'a');
+        m.insert(2, 'b');
+        assert_eq!(m.remove_one(&1), Some('a'));
+        assert!(!m.contains_key(&1));
+        assert_eq!(m.remove_one(&1), None);
+        assert_eq!(m.length, 1);
+    }
+
+    #[test]
+    fn remove_all() {
+        let mut m = ABRMulti::new();
+        m.insert(1, 'a');
+        m.insert(2, 'b');
+        m.insert(1, 'c');
+        assert_eq!(m.remove_all(&1), vec!['a', 'c']);
+        assert!(m.remove_all(&1).is_empty());
+        assert_eq!(m.length, 1);
+    }
+
+    #[test]
+    fn iter_groups_duplicates() {
+        let mut m = ABRMulti::new();
+        for &(k, v) in &[(3, 'a'), (1, 'b'), (3, 'c'), (2, 'd'), (1, 'e')] {
+            m.insert(k, v);
+        }
+        let entries: Vec<_> = m.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(
+            entries,
+            vec![(1, 'b'), (1, 'e'), (2, 'd'), (3, 'a'), (3, 'c')]
+        );
+        assert_eq!(ABRMulti::<u32, u32>::new().iter().count(), 0);
+    }
+}
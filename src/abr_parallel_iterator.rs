@@ -1,103 +1,400 @@
 extern crate rayon_adaptive;
 use crate::abr::ABR;
 use crate::abr_iterator::ABRIterator;
+use crate::augment::Augment;
 use crate::node::BoxedNode;
 use rayon_adaptive::prelude::*;
 use rayon_adaptive::BasicPower;
-use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
-pub type RefNode<'a, K, V> = &'a BoxedNode<K, V>;
+pub type RefNode<'a, K, V, A = ()> = &'a BoxedNode<K, V, A>;
 
-pub struct ABRParallelIterator<'a, K, V> {
-    small_nodes: Vec<RefNode<'a, K, V>>,
-    big_nodes: VecDeque<RefNode<'a, K, V>>,
+/// A parallel (using rayon_adaptive) in-order iterator over an
+/// [ABR]{struct.ABR.html}.
+///
+/// The nodes are divided exactly where asked, using the sizes of the
+/// subtrees to skip over the front part.
+#[derive(Debug)]
+pub struct ABRParallelIterator<'a, K, V, A = ()>
+where
+    A: Augment<K, V>,
+{
+    iterator: ABRIterator<'a, K, V, A>,
 }
 
-impl<'a, K, V> ABRParallelIterator<'a, K, V>
+impl<'a, K, V, A> ABRParallelIterator<'a, K, V, A>
 where
     K: Ord,
+    A: Augment<K, V>,
 {
-    pub fn new(tree: &'a ABR<K, V>) -> ABRParallelIterator<K, V> {
-        let mut small_nodes: Vec<RefNode<'a, K, V>> = Vec::new();
-        let mut big_nodes: VecDeque<RefNode<'a, K, V>> = VecDeque::new();
-
-        ABRIterator::descent(
-            &mut small_nodes,
-            &mut big_nodes,
-            &tree.root.as_ref().unwrap(),
-        );
-
+    pub fn new(tree: &'a ABR<K, V, A>) -> ABRParallelIterator<K, V, A> {
         ABRParallelIterator {
-            small_nodes,
-            big_nodes,
+            iterator: ABRIterator::new(tree),
         }
     }
 }
 
-impl<'a, K, V> Divisible for ABRParallelIterator<'a, K, V>
+impl<'a, K, V, A> Divisible for ABRParallelIterator<'a, K, V, A>
 where
     K: Ord,
+    A: Augment<K, V>,
 {
     type Power = BasicPower;
 
     fn base_length(&self) -> Option<usize> {
-        Some(
-            (1 - self.small_nodes.is_empty() as usize)
-                + self.big_nodes.len()
-                + self
-                    .big_nodes
-                    .back()
-                    .map(|n| n.has_right_child() as usize)
-                    .unwrap_or(0),
-        )
-    }
-
-    fn divide_at(mut self, _index: usize) -> (Self, Self) {
-        let given_node = match self.big_nodes.len() {
-            0 => None,
-            1 => {
-                if self.small_nodes.is_empty() {
-                    let node = self.big_nodes.pop_back().unwrap();
-                    self.small_nodes.push(node);
-
-                    let node_right = node.children[1].as_ref().unwrap();
-
-                    ABRIterator::descent(&mut self.small_nodes, &mut self.big_nodes, &node_right);
-                }
-                self.big_nodes.pop_back()
-            }
-            _ => self.big_nodes.pop_back(),
-        };
+        Some(self.iterator.remaining)
+    }
 
-        (
-            self,
-            ABRParallelIterator {
-                small_nodes: Vec::new(),
-                big_nodes: given_node.into_iter().collect(),
-            },
-        )
+    fn divide_at(mut self, index: usize) -> (Self, Self) {
+        let front = self.iterator.split_off_front(index);
+        (ABRParallelIterator { iterator: front }, self)
     }
 }
 
-impl<'a, K, V> ParallelIterator for ABRParallelIterator<'a, K, V>
+impl<'a, K, V, A> ParallelIterator for ABRParallelIterator<'a, K, V, A>
 where
     K: Sync + Ord,
     V: Sync,
+    A: Augment<K, V>,
+    A::Summary: Sync,
 {
-    type Item = &'a BoxedNode<K, V>;
+    type Item = &'a BoxedNode<K, V, A>;
 
-    type SequentialIterator = ABRIterator<'a, K, V>;
+    type SequentialIterator = ABRIterator<'a, K, V, A>;
 
     fn to_sequential(self) -> Self::SequentialIterator {
-        ABRIterator {
-            small_nodes: self.small_nodes.into_iter(),
-            big_nodes: self.big_nodes,
-        }
+        self.iterator
     }
 
-    #[allow(unused_variables)]
     fn extract_iter(&mut self, size: usize) -> Self::SequentialIterator {
-        //self.borrow_divide().to_sequential()
-        panic!("extract_iter");
+        self.iterator.split_off_front(size)
+    }
+}
+
+impl<K, V, A> ABR<K, V, A>
+where
+    K: Sync + Ord,
+    V: Sync,
+    A: Augment<K, V>,
+    A::Summary: Sync,
+{
+    /// Searches in parallel (using rayon_adaptive) for a node matching
+    /// `predicate`, returning any of them.
+    ///
+    /// All the tasks stop as soon as one of them found a match.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<u32, ()> = (0..1000).collect();
+    /// let found = tree.par_find_any(|n| n.key % 100 == 42).unwrap();
+    /// assert_eq!(found.key % 100, 42);
+    /// assert!(tree.par_find_any(|n| n.key > 1000).is_none());
+    /// ```
+    pub fn par_find_any<P>(&self, predicate: P) -> Option<&BoxedNode<K, V, A>>
+    where
+        P: Fn(&BoxedNode<K, V, A>) -> bool + Sync,
+    {
+        find_any(self.adaptive_par_iter(), &|node: &&BoxedNode<K, V, A>| {
+            predicate(node)
+        })
+    }
+
+    /// Searches in parallel (using rayon_adaptive) for the first node, in
+    /// order, matching `predicate`.
+    ///
+    /// Once a match is found, the tasks stop as soon as they reach nodes
+    /// coming after it.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<u32, ()> = (0..1000).collect();
+    /// assert_eq!(tree.par_find_first(|n| n.key % 100 == 42).unwrap().key, 42);
+    /// ```
+    pub fn par_find_first<P>(&self, predicate: P) -> Option<&BoxedNode<K, V, A>>
+    where
+        P: Fn(&BoxedNode<K, V, A>) -> bool + Sync,
+    {
+        find_first(self, self.adaptive_par_iter(), &predicate)
+    }
+
+    /// Tells in parallel (using rayon_adaptive) whether a node matches
+    /// `predicate`, stopping as soon as one does.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<u32, ()> = (0..1000).collect();
+    /// assert!(tree.par_any(|n| n.key == 500));
+    /// assert!(!tree.par_any(|n| n.key == 5000));
+    /// ```
+    pub fn par_any<P>(&self, predicate: P) -> bool
+    where
+        P: Fn(&BoxedNode<K, V, A>) -> bool + Sync,
+    {
+        self.par_find_any(predicate).is_some()
+    }
+
+    /// Tells in parallel (using rayon_adaptive) whether all the nodes
+    /// match `predicate`, stopping as soon as one does not.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<u32, ()> = (0..1000).collect();
+    /// assert!(tree.par_all(|n| n.key < 1000));
+    /// assert!(!tree.par_all(|n| n.key != 500));
+    /// ```
+    pub fn par_all<P>(&self, predicate: P) -> bool
+    where
+        P: Fn(&BoxedNode<K, V, A>) -> bool + Sync,
+    {
+        !self.par_any(|node| !predicate(node))
+    }
+}
+
+/// Finds an item matching `predicate`, every sequential part of the
+/// iterator stopping as soon as any of them found one.
+///
+/// The flag is checked before each item, so the cancellation does not
+/// depend on the way the iterator is divided, whatever the policy.
+pub(crate) fn find_any<I, P>(iterator: I, predicate: &P) -> Option<I::Item>
+where
+    I: ParallelIterator,
+    P: Fn(&I::Item) -> bool + Sync,
+{
+    let found = AtomicBool::new(false);
+    iterator
+        .iterator_fold(|items| {
+            let item = items
+                .take_while(|_| !found.load(Ordering::Relaxed))
+                .find(|item| predicate(item));
+            if item.is_some() {
+                found.store(true, Ordering::Relaxed);
+            }
+            item
+        })
+        .reduce(|| None, |a, b| a.or(b))
+}
+
+/// Finds the first node (in order) matching `predicate`.
+///
+/// The smallest position of a match found so far is shared : each
+/// sequential part stops as soon as it goes past it. The sequential parts
+/// know their own position, so no rank is computed along the way.
+fn find_first<'a, K, V, A, P>(
+    tree: &'a ABR<K, V, A>,
+    iterator: ABRParallelIterator<'a, K, V, A>,
+    predicate: &P,
+) -> Option<RefNode<'a, K, V, A>>
+where
+    K: Sync + Ord,
+    V: Sync,
+    A: Augment<K, V>,
+    A::Summary: Sync,
+    P: Fn(&BoxedNode<K, V, A>) -> bool + Sync,
+{
+    let first = AtomicUsize::new(usize::MAX);
+    iterator
+        .iterator_fold(|nodes| {
+            for (position, node) in (nodes.position..).zip(nodes) {
+                if position > first.load(Ordering::Relaxed) {
+                    break;
+                }
+                if predicate(node) {
+                    first.fetch_min(position, Ordering::Relaxed);
+                    break;
+                }
+            }
+        })
+        .reduce(|| (), |_, _| ());
+    match first.into_inner() {
+        usize::MAX => None,
+        position => tree.nth(position),
+    }
+}
+
+#[cfg(test)]
+mod abr_parallel_iterator_tests {
+    use super::*;
+    use rayon_adaptive::Policy;
+
+    fn random_tree(size: usize) -> ABR<u64, ()> {
+        let mut x: u64 = 5;
+        (0..size)
+            .map(|_| {
+                x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                x >> 20
+            })
+            .collect()
+    }
+
+    #[test]
+    fn extract_iter() {
+        let tree = random_tree(1000);
+        let keys: Vec<u64> = tree.iter().map(|n| n.key).collect();
+        for &size in &[1, 2, 10, 100, 999, 1000, 5000] {
+            let mut iterator = tree.adaptive_par_iter();
+            let mut extracted = Vec::new();
+            loop {
+                let chunk: Vec<u64> = iterator.extract_iter(size).map(|n| n.key).collect();
+                if chunk.is_empty() {
+                    break;
+                }
+                assert_eq!(chunk.len(), size.min(keys.len() - extracted.len()));
+                extracted.extend(chunk);
+            }
+            assert_eq!(extracted, keys);
+        }
+    }
+
+    #[test]
+    fn divide_at() {
+        let tree = random_tree(1000);
+        let keys: Vec<u64> = tree.iter().map(|n| n.key).collect();
+        let n = keys.len();
+        for &index in &[0, 1, 2, 17, n / 2, n - 1, n] {
+            let (left, right) = tree.adaptive_par_iter().divide_at(index);
+            assert_eq!(left.base_length(), Some(index));
+            assert_eq!(right.base_length(), Some(n - index));
+            let (left, right) = (left.to_sequential(), right.to_sequential());
+            assert!(left.chain(right).map(|n| n.key).eq(keys.iter().cloned()));
+        }
+        // dividing again each part keeps the order
+        let (left, right) = tree.adaptive_par_iter().divide_at(n / 3);
+        let (a, b) = left.divide_at(n / 7);
+        let (c, d) = right.divide_at(n / 5);
+        let parts = vec![a, b, c, d];
+        assert_eq!(
+            parts
+                .iter()
+                .map(|p| p.base_length().unwrap())
+                .collect::<Vec<_>>(),
+            vec![n / 7, n / 3 - n / 7, n / 5, n - n / 3 - n / 5]
+        );
+        assert!(parts
+            .into_iter()
+            .flat_map(|p| p.to_sequential())
+            .map(|n| n.key)
+            .eq(keys.iter().cloned()));
+    }
+
+    #[test]
+    fn skewed_tree() {
+        // a linked list leaning right: the old split gave a single node away
+        let tree: ABR<u32, ()> = (0..500).collect();
+        let (left, right) = tree.adaptive_par_iter().divide_at(250);
+        assert!(left.to_sequential().map(|n| n.key).eq(0..250));
+        assert!(right.to_sequential().map(|n| n.key).eq(250..500));
+    }
+
+    fn policies() -> Vec<Policy> {
+        vec![
+            Policy::Sequential,
+            Policy::DefaultPolicy,
+            Policy::Join(1),
+            Policy::Join(100),
+            Policy::JoinContext(1),
+            Policy::JoinContext(100),
+            Policy::Adaptive(1, 100),
+            Policy::Adaptive(10, 1000),
+        ]
+    }
+
+    #[test]
+    fn all_policies() {
+        let tree = random_tree(10_000);
+        let expected: u64 = tree.iter().map(|n| n.key % 1000).sum();
+        for policy in policies() {
+            let sum = tree
+                .adaptive_par_iter()
+                .with_policy(policy)
+                .map(|n| n.key % 1000)
+                .reduce(|| 0, |a, b| a + b);
+            assert_eq!(sum, expected);
+        }
+    }
+
+    #[test]
+    fn all_policies_keep_order() {
+        let tree = random_tree(10_000);
+        for policy in policies() {
+            let keys: Vec<u64> = tree
+                .adaptive_par_iter()
+                .with_policy(policy)
+                .iterator_fold(|i| i.map(|n| n.key).collect::<Vec<u64>>())
+                .reduce(Vec::new, |mut a, b| {
+                    a.extend(b);
+                    a
+                });
+            assert!(keys.into_iter().eq(tree.iter().map(|n| n.key)));
+        }
+    }
+
+    #[test]
+    fn find_all_policies() {
+        let tree = random_tree(100_000);
+        let keys: Vec<u64> = tree.iter().map(|n| n.key).collect();
+        let target = keys[keys.len() / 3];
+        for policy in policies() {
+            let calls = AtomicUsize::new(0);
+            // every node matches : the tasks must stop right away
+            let found = find_any(tree.adaptive_par_iter().with_policy(policy), &|_| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                true
+            });
+            assert!(found.is_some());
+            assert!(calls.into_inner() < keys.len() / 10);
+
+            let found = find_any(tree.adaptive_par_iter().with_policy(policy), &|n| {
+                n.key == target
+            });
+            assert_eq!(found.map(|n| n.key), Some(target));
+
+            let first = find_first(&tree, tree.adaptive_par_iter().with_policy(policy), &|n| {
+                n.key >= target
+            });
+            assert_eq!(first.map(|n| n.key), Some(target));
+            assert!(
+                find_first(&tree, tree.adaptive_par_iter().with_policy(policy), &|_| {
+                    false
+                })
+                .is_none()
+            );
+        }
+    }
+
+    #[test]
+    fn collect_all_policies() {
+        let keys = random_tree(10_000);
+        let mut expected: ABR<u64, u64> = ABR::new();
+        for n in keys.iter() {
+            expected.insert(n.key % 3000, n.key);
+        }
+        for policy in policies() {
+            let tree: ABR<u64, u64> = ABR::from_adaptive_par_iter(
+                keys.adaptive_par_iter()
+                    .with_policy(policy)
+                    .map(|n| (n.key % 3000, n.key)),
+            );
+            assert_eq!(tree.length, expected.length);
+            assert!(tree
+                .iter()
+                .map(|n| (n.key, n.value))
+                .eq(expected.iter().map(|n| (n.key, n.value))));
+        }
     }
 }
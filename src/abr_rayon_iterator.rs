@@ -0,0 +1,247 @@
+use crate::abr::{merge_runs, sorted_run, ABR};
+use crate::abr_iterator::ABRIterator;
+use crate::augment::Augment;
+use crate::node::BoxedNode;
+use rayon::iter::plumbing::{bridge_unindexed, Folder, UnindexedConsumer, UnindexedProducer};
+use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelIterator};
+
+/// A parallel (using rayon) in-order iterator over an [ABR]{struct.ABR.html}.
+///
+/// Each split hands exactly half of the remaining nodes to each part,
+/// using the sizes of the subtrees.
+#[derive(Debug)]
+pub struct ABRRayonIterator<'a, K, V, A = ()>
+where
+    A: Augment<K, V>,
+{
+    iterator: ABRIterator<'a, K, V, A>,
+}
+
+impl<'a, K, V, A> ABRRayonIterator<'a, K, V, A>
+where
+    K: Ord,
+    A: Augment<K, V>,
+{
+    pub fn new(tree: &'a ABR<K, V, A>) -> ABRRayonIterator<'a, K, V, A> {
+        ABRRayonIterator {
+            iterator: ABRIterator::new(tree),
+        }
+    }
+}
+
+impl<'a, K, V, A> ParallelIterator for ABRRayonIterator<'a, K, V, A>
+where
+    K: Sync + Ord,
+    V: Sync,
+    A: Augment<K, V>,
+    A::Summary: Sync,
+{
+    type Item = &'a BoxedNode<K, V, A>;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        bridge_unindexed(self, consumer)
+    }
+}
+
+impl<'a, K, V, A> UnindexedProducer for ABRRayonIterator<'a, K, V, A>
+where
+    K: Sync + Ord,
+    V: Sync,
+    A: Augment<K, V>,
+    A::Summary: Sync,
+{
+    type Item = &'a BoxedNode<K, V, A>;
+
+    fn split(mut self) -> (Self, Option<Self>) {
+        let length = self.iterator.remaining;
+        if length < 2 {
+            return (self, None);
+        }
+        let front = self.iterator.split_off_front(length / 2);
+        (ABRRayonIterator { iterator: front }, Some(self))
+    }
+
+    fn fold_with<F>(self, folder: F) -> F
+    where
+        F: Folder<Self::Item>,
+    {
+        folder.consume_iter(self.iterator)
+    }
+}
+
+/// Iterates in parallel (using rayon) over the nodes of a tree.
+///
+/// # Examples
+///
+/// Basic usage :
+/// ```
+/// use abr::abr::ABR;
+/// use rayon::prelude::*;
+///
+/// let tree : ABR<_, _> = vec![5, 3, 7, 1, 4, 2, 6].into_iter().collect();
+/// let sum: u32 = tree.into_par_iter().map(|n| n.key).sum();
+/// assert_eq!(sum, 28);
+/// ```
+impl<'a, K, V, A> IntoParallelIterator for &'a ABR<K, V, A>
+where
+    K: Sync + Ord,
+    V: Sync,
+    A: Augment<K, V>,
+    A::Summary: Sync,
+{
+    type Iter = ABRRayonIterator<'a, K, V, A>;
+    type Item = &'a BoxedNode<K, V, A>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        ABRRayonIterator::new(self)
+    }
+}
+
+/// Collects key and value pairs in parallel (using rayon) into a tree.
+///
+/// Each task sorts the entries it gets into a run, the runs are merged
+/// and the tree is built balanced from the result. As with `insert`, the
+/// last value of a key wins.
+///
+/// # Examples
+///
+/// Basic usage :
+/// ```
+/// use abr::abr::ABR;
+/// use rayon::prelude::*;
+///
+/// let tree: ABR<u32, u32> = (0..100u32).into_par_iter().map(|k| (k % 10, k)).collect();
+/// assert_eq!(tree.length, 10);
+/// assert_eq!(tree.get(&3), Some(&93));
+/// ```
+impl<K, V, A> FromParallelIterator<(K, V)> for ABR<K, V, A>
+where
+    K: Ord + Send,
+    V: Send,
+    A: Augment<K, V>,
+{
+    fn from_par_iter<I>(iterator: I) -> Self
+    where
+        I: IntoParallelIterator<Item = (K, V)>,
+    {
+        let entries = iterator
+            .into_par_iter()
+            .fold(Vec::new, |mut run, entry| {
+                run.push(entry);
+                run
+            })
+            .map(sorted_run)
+            .reduce(Vec::new, merge_runs);
+        ABR::from_sorted(entries)
+    }
+}
+
+/// Collects keys in parallel (using rayon) into a tree without values.
+///
+/// # Examples
+///
+/// Basic usage :
+/// ```
+/// use abr::abr::ABR;
+/// use rayon::prelude::*;
+///
+/// let tree: ABR<u32, ()> = (0..100u32).into_par_iter().map(|k| k % 10).collect();
+/// assert!(tree.iter().map(|n| n.key).eq(0..10));
+/// ```
+impl<K, A> FromParallelIterator<K> for ABR<K, (), A>
+where
+    K: Ord + Send,
+    A: Augment<K, ()>,
+{
+    fn from_par_iter<I>(iterator: I) -> Self
+    where
+        I: IntoParallelIterator<Item = K>,
+    {
+        iterator.into_par_iter().map(|key| (key, ())).collect()
+    }
+}
+
+#[cfg(test)]
+mod abr_rayon_iterator_tests {
+    use super::*;
+    use crate::node::OptBoxedNode;
+    use rayon::iter::IndexedParallelIterator;
+
+    fn random_tree(size: usize) -> ABR<u64, ()> {
+        let mut x: u64 = 5;
+        (0..size)
+            .map(|_| {
+                x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                x >> 20
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sum_and_order() {
+        let tree = random_tree(10_000);
+        let expected: u64 = tree.iter().map(|n| n.key % 1000).sum();
+        let sum: u64 = tree.into_par_iter().map(|n| n.key % 1000).sum();
+        assert_eq!(sum, expected);
+
+        let keys: Vec<u64> = tree.into_par_iter().map(|n| n.key).collect();
+        assert!(keys.into_iter().eq(tree.iter().map(|n| n.key)));
+    }
+
+    #[test]
+    fn stock_combinators() {
+        let tree: ABR<u32, ()> = (0..1000).collect();
+        assert_eq!(tree.into_par_iter().count(), 1000);
+        assert_eq!(tree.into_par_iter().filter(|n| n.key % 3 == 0).count(), 334);
+        assert_eq!(
+            tree.into_par_iter()
+                .find_first(|n| n.key > 500)
+                .map(|n| n.key),
+            Some(501)
+        );
+        assert_eq!(tree.into_par_iter().map(|n| n.key).max(), Some(999));
+        assert_eq!(ABR::<u32, ()>::new().into_par_iter().count(), 0);
+    }
+
+    fn height<K, V>(node: &OptBoxedNode<K, V>) -> usize {
+        node.as_ref().map_or(0, |n| {
+            1 + height(&n.children[0]).max(height(&n.children[1]))
+        })
+    }
+
+    #[test]
+    fn collect() {
+        let entries: Vec<(u64, usize)> = random_tree(10_000)
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.key % 3000, i))
+            .collect();
+        let mut expected: ABR<u64, usize> = ABR::new();
+        for &(key, value) in &entries {
+            expected.insert(key, value);
+        }
+
+        let tree: ABR<u64, usize> = entries.into_par_iter().collect();
+        assert_eq!(tree.length, expected.length);
+        assert!(tree
+            .iter()
+            .map(|n| (n.key, n.value))
+            .eq(expected.iter().map(|n| (n.key, n.value))));
+        assert_eq!(tree.root.as_ref().unwrap().size, tree.length);
+        // 2^12 > 3000
+        assert!(height(&tree.root) <= 12);
+
+        let keys: ABR<u32, ()> = (0..1000u32).into_par_iter().rev().collect();
+        assert!(keys.iter().map(|n| n.key).eq(0..1000));
+    }
+
+    #[test]
+    fn par_iter() {
+        use rayon::iter::IntoParallelRefIterator;
+        let tree: ABR<u32, ()> = (0..100).collect();
+        assert_eq!(tree.par_iter().map(|n| n.key).sum::<u32>(), 4950);
+    }
+}
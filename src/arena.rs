@@ -0,0 +1,608 @@
+#[cfg(feature = "rayon_adaptive")]
+use rayon_adaptive::prelude::*;
+#[cfg(feature = "rayon_adaptive")]
+use rayon_adaptive::BasicPower;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+
+/// The index of a node in the arena.
+pub type NodeIndex = u32;
+
+/// The index standing for a missing child.
+pub const NIL: NodeIndex = NodeIndex::MAX;
+
+/// A node in an arena-backed binary search tree
+///
+/// Children and parent are designated by their index in the arena,
+/// `NIL` if absent. `size` is the number of nodes of the subtree.
+#[derive(Debug, Clone)]
+pub struct ArenaNode<K, V> {
+    pub key: K,
+    pub value: V,
+    pub children: [NodeIndex; 2],
+    pub parent: NodeIndex,
+    pub size: u32,
+}
+
+/// A binary search tree whose nodes are stored contiguously
+///
+/// All the nodes live in a single `Vec` and refer to each other by their
+/// `u32` index, instead of being allocated separately. The vector stays
+/// compact : removing a node moves the last one into its slot.
+///
+/// Nodes also know their parent, so that moving from a node to the next
+/// one needs no stack : iterating does not allocate. This is the only
+/// tree of the crate with parent links, the boxed nodes of `ABR` have none.
+///
+/// # Examples
+/// Basic usage :
+///
+/// ```
+/// use abr::arena::ArenaABR;
+///
+/// let mut tree = ArenaABR::new();
+/// tree.insert(2, "world");
+/// tree.insert(1, "hello");
+///
+/// assert_eq!(tree.get(&1), Some(&"hello"));
+/// assert_eq!(tree.remove(&2), Some("world"));
+/// assert_eq!(tree.len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ArenaABR<K, V> {
+    pub nodes: Vec<ArenaNode<K, V>>,
+    pub root: NodeIndex,
+}
+
+impl<K, V> Default for ArenaABR<K, V> {
+    fn default() -> Self {
+        ArenaABR {
+            nodes: Vec::new(),
+            root: NIL,
+        }
+    }
+}
+
+/// Enables collection into an arena tree, as for `ABR`.
+///
+/// ```
+/// use abr::arena::ArenaABR;
+///
+/// let tree: ArenaABR<_, _> = vec![5, 3, 7, 1].into_iter().collect();
+/// assert!(tree.iter().map(|n| n.key).eq(vec![1, 3, 5, 7]));
+/// ```
+impl<K> std::iter::FromIterator<K> for ArenaABR<K, ()>
+where
+    K: Ord,
+{
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = K>,
+    {
+        let mut a = ArenaABR::new();
+        for key in iter {
+            a.insert(key, ());
+        }
+        a
+    }
+}
+
+impl<K, V> ArenaABR<K, V>
+where
+    K: Ord,
+{
+    /// Create a new, empty arena tree.
+    pub fn new() -> ArenaABR<K, V> {
+        ArenaABR::default()
+    }
+
+    /// Create a new, empty arena tree with room for `capacity` nodes.
+    pub fn with_capacity(capacity: usize) -> ArenaABR<K, V> {
+        ArenaABR {
+            nodes: Vec::with_capacity(capacity),
+            root: NIL,
+        }
+    }
+
+    /// Returns the number of nodes in the tree.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the index of the node holding a key, along with the index
+    /// of its parent (`NIL` for the root) and its direction from it.
+    fn find<Q>(&self, key: &Q) -> (NodeIndex, NodeIndex, usize)
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (mut parent, mut direction) = (NIL, 0);
+        let mut current = self.root;
+        while current != NIL {
+            let node = &self.nodes[current as usize];
+            direction = match key.cmp(node.key.borrow()) {
+                Ordering::Equal => break,
+                Ordering::Less => 0,
+                Ordering::Greater => 1,
+            };
+            parent = current;
+            current = node.children[direction];
+        }
+        (current, parent, direction)
+    }
+
+    /// Points the link from `parent` in `direction` (the root link if
+    /// `parent` is `NIL`) to `child`, and back.
+    fn link(&mut self, parent: NodeIndex, direction: usize, child: NodeIndex) {
+        if parent == NIL {
+            self.root = child;
+        } else {
+            self.nodes[parent as usize].children[direction] = child;
+        }
+        if child != NIL {
+            self.nodes[child as usize].parent = parent;
+        }
+    }
+
+    /// Adds `delta` to the sizes of the subtrees from `start` up to
+    /// `stop` (excluded, `NIL` for all the ancestors), through the parents.
+    fn resize_path(&mut self, mut start: NodeIndex, stop: NodeIndex, delta: i64) {
+        while start != stop {
+            let node = &mut self.nodes[start as usize];
+            node.size = (i64::from(node.size) + delta) as u32;
+            start = node.parent;
+        }
+    }
+
+    /// Inserts a key and value pair in the tree
+    ///
+    /// If the key was already present, its previous value is returned.
+    ///
+    /// # Panics
+    /// The function will panic if the tree already holds `u32::MAX`
+    /// nodes.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let (index, parent, direction) = self.find(&key);
+        if index != NIL {
+            return Some(std::mem::replace(
+                &mut self.nodes[index as usize].value,
+                value,
+            ));
+        }
+
+        let new_index = self.nodes.len() as NodeIndex;
+        assert!(new_index != NIL, "arena is full");
+        self.nodes.push(ArenaNode {
+            key,
+            value,
+            children: [NIL, NIL],
+            parent: NIL,
+            size: 1,
+        });
+        self.link(parent, direction, new_index);
+        self.resize_path(parent, NIL, 1);
+        None
+    }
+
+    /// Returns the value associated to a key, if present.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.find(key).0 {
+            NIL => None,
+            index => Some(&self.nodes[index as usize].value),
+        }
+    }
+
+    /// Returns a mutable reference to the value associated to a key,
+    /// if present.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.find(key).0 {
+            NIL => None,
+            index => Some(&mut self.nodes[index as usize].value),
+        }
+    }
+
+    /// Returns `true` if the specified key is contained in the tree.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key).0 != NIL
+    }
+
+    /// Removes a key from the tree, returning its value if present.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (index, parent, direction) = self.find(key);
+        if index == NIL {
+            return None;
+        }
+
+        let [left, right] = self.nodes[index as usize].children;
+        if left == NIL || right == NIL {
+            self.link(parent, direction, if left == NIL { right } else { left });
+        } else {
+            let size = self.nodes[index as usize].size;
+            // the minimum of the right subtree takes the place of the node
+            let (mut min_parent, mut min_index) = (index, right);
+            while self.nodes[min_index as usize].children[0] != NIL {
+                min_parent = min_index;
+                min_index = self.nodes[min_index as usize].children[0];
+            }
+            let min_right = self.nodes[min_index as usize].children[1];
+            self.link(min_parent, (min_parent == index) as usize, min_right);
+            let [left, right] = self.nodes[index as usize].children;
+            self.link(min_index, 0, left);
+            self.link(min_index, 1, right);
+            self.link(parent, direction, min_index);
+            // the subtree of the minimum is now the one of the node, minus it
+            self.nodes[min_index as usize].size = size - 1;
+            if min_parent != index {
+                self.resize_path(min_parent, min_index, -1);
+            }
+        }
+        self.resize_path(parent, NIL, -1);
+
+        Some(self.free(index).value)
+    }
+
+    /// Takes a node out of the arena, moving the last node into its slot.
+    ///
+    /// The freed node must not be linked anymore.
+    fn free(&mut self, index: NodeIndex) -> ArenaNode<K, V> {
+        let last = (self.nodes.len() - 1) as NodeIndex;
+        let node = self.nodes.swap_remove(index as usize);
+        if index != last {
+            // relink the moved node from its parent and children
+            let ArenaNode {
+                parent, children, ..
+            } = self.nodes[index as usize];
+            let direction =
+                (parent != NIL && self.nodes[parent as usize].children[1] == last) as usize;
+            self.link(parent, direction, index);
+            self.link(index, 0, children[0]);
+            self.link(index, 1, children[1]);
+        }
+        node
+    }
+
+    /// Returns the index of the node holding a key, if present.
+    pub fn index_of<Q>(&self, key: &Q) -> Option<NodeIndex>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.find(key).0 {
+            NIL => None,
+            index => Some(index),
+        }
+    }
+
+    /// Get a parallel iterator (using rayon_adaptive) from the tree.
+    /// ```
+    /// use abr::arena::ArenaABR;
+    /// use rayon_adaptive::prelude::*;
+    ///
+    /// let tree: ArenaABR<_, _> = vec![5, 3, 7, 1, 4, 2, 6].into_iter().collect();
+    /// assert_eq!(tree.par_iter().map(|n| n.key).reduce(|| 0, |a, b| a + b), 28);
+    /// ```
+    #[cfg(feature = "rayon_adaptive")]
+    pub fn par_iter<'a>(&'a self) -> ArenaParallelIterator<'a, K, V> {
+        ArenaParallelIterator {
+            arena: self,
+            start: 0,
+            end: self.len(),
+        }
+    }
+}
+
+impl<K, V> ArenaABR<K, V> {
+    /// Returns the last node reached from `start` going in `direction`.
+    fn extreme(&self, mut start: NodeIndex, direction: usize) -> NodeIndex {
+        while self.nodes[start as usize].children[direction] != NIL {
+            start = self.nodes[start as usize].children[direction];
+        }
+        start
+    }
+
+    /// Returns the neighbour of a node in `direction` : 1 for the next
+    /// node, 0 for the previous one, `NIL` if there is none.
+    fn neighbour(&self, index: NodeIndex, direction: usize) -> NodeIndex {
+        let child = self.nodes[index as usize].children[direction];
+        if child != NIL {
+            return self.extreme(child, 1 - direction);
+        }
+        // first ancestor we reach from its other side
+        let (mut from, mut parent) = (index, self.nodes[index as usize].parent);
+        while parent != NIL && self.nodes[parent as usize].children[direction] == from {
+            from = parent;
+            parent = self.nodes[parent as usize].parent;
+        }
+        parent
+    }
+
+    fn to_option(index: NodeIndex) -> Option<NodeIndex> {
+        match index {
+            NIL => None,
+            index => Some(index),
+        }
+    }
+
+    /// Returns the index of the node with the smallest key.
+    pub fn first(&self) -> Option<NodeIndex> {
+        Self::to_option(self.root).map(|root| self.extreme(root, 0))
+    }
+
+    /// Returns the index of the node with the greatest key.
+    pub fn last(&self) -> Option<NodeIndex> {
+        Self::to_option(self.root).map(|root| self.extreme(root, 1))
+    }
+
+    /// Returns the index of the node at the given position in order,
+    /// found using the sizes of the subtrees.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::arena::ArenaABR;
+    ///
+    /// let tree: ArenaABR<_, _> = vec![5, 3, 7, 1].into_iter().collect();
+    /// let second = tree.nth(1).unwrap();
+    ///
+    /// assert_eq!(tree.nodes[second as usize].key, 3);
+    /// assert!(tree.nth(4).is_none());
+    /// ```
+    pub fn nth(&self, mut position: usize) -> Option<NodeIndex> {
+        let mut current = self.root;
+        while current != NIL {
+            let node = &self.nodes[current as usize];
+            let left = match node.children[0] {
+                NIL => 0,
+                child => self.nodes[child as usize].size as usize,
+            };
+            current = match position.cmp(&left) {
+                Ordering::Equal => return Some(current),
+                Ordering::Less => node.children[0],
+                Ordering::Greater => {
+                    position -= left + 1;
+                    node.children[1]
+                }
+            };
+        }
+        None
+    }
+
+    /// Returns the index of the node following the given one, in order.
+    ///
+    /// The parent links are followed : no memory is allocated.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::arena::ArenaABR;
+    ///
+    /// let tree: ArenaABR<_, _> = vec![5, 3, 7, 1].into_iter().collect();
+    /// let three = tree.index_of(&3).unwrap();
+    /// let five = tree.successor(three).unwrap();
+    ///
+    /// assert_eq!(tree.nodes[five as usize].key, 5);
+    /// assert_eq!(tree.predecessor(five), Some(three));
+    /// ```
+    pub fn successor(&self, index: NodeIndex) -> Option<NodeIndex> {
+        Self::to_option(self.neighbour(index, 1))
+    }
+
+    /// Returns the index of the node preceding the given one, in order.
+    pub fn predecessor(&self, index: NodeIndex) -> Option<NodeIndex> {
+        Self::to_option(self.neighbour(index, 0))
+    }
+
+    /// Returns an in-order iterator over the nodes of the tree.
+    ///
+    /// The iterator follows the parent links, it does not allocate.
+    pub fn iter(&self) -> ArenaIterator<'_, K, V> {
+        ArenaIterator {
+            arena: self,
+            current: self.first().unwrap_or(NIL),
+            end: NIL,
+        }
+    }
+}
+
+/// A sequential iterator for the [ArenaABR]{struct.ArenaABR.html} structure.
+///
+/// It goes from `current` to `end` (excluded, `NIL` for the end of the
+/// tree) through the successors of the nodes.
+#[derive(Debug)]
+pub struct ArenaIterator<'a, K, V> {
+    arena: &'a ArenaABR<K, V>,
+    current: NodeIndex,
+    end: NodeIndex,
+}
+
+impl<'a, K, V> Iterator for ArenaIterator<'a, K, V> {
+    type Item = &'a ArenaNode<K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current == self.end {
+            return None;
+        }
+        let index = self.current;
+        self.current = self.arena.neighbour(index, 1);
+        Some(&self.arena.nodes[index as usize])
+    }
+}
+
+/// A parallel iterator (using rayon_adaptive) for the
+/// [ArenaABR]{struct.ArenaABR.html} structure.
+///
+/// It covers the nodes from position `start` to `end` (excluded), in
+/// order. It is divided exactly where asked, the nodes at its bounds being
+/// found using the sizes of the subtrees.
+#[cfg(feature = "rayon_adaptive")]
+pub struct ArenaParallelIterator<'a, K, V> {
+    arena: &'a ArenaABR<K, V>,
+    start: usize,
+    end: usize,
+}
+
+#[cfg(feature = "rayon_adaptive")]
+impl<'a, K, V> Divisible for ArenaParallelIterator<'a, K, V> {
+    type Power = BasicPower;
+
+    fn base_length(&self) -> Option<usize> {
+        Some(self.end - self.start)
+    }
+
+    fn divide_at(self, index: usize) -> (Self, Self) {
+        let middle = self.end.min(self.start + index);
+        (
+            ArenaParallelIterator {
+                arena: self.arena,
+                start: self.start,
+                end: middle,
+            },
+            ArenaParallelIterator {
+                arena: self.arena,
+                start: middle,
+                end: self.end,
+            },
+        )
+    }
+}
+
+#[cfg(feature = "rayon_adaptive")]
+impl<'a, K, V> ParallelIterator for ArenaParallelIterator<'a, K, V>
+where
+    K: Sync,
+    V: Sync,
+{
+    type Item = &'a ArenaNode<K, V>;
+
+    type SequentialIterator = ArenaIterator<'a, K, V>;
+
+    fn to_sequential(self) -> Self::SequentialIterator {
+        let arena = self.arena;
+        ArenaIterator {
+            arena,
+            current: arena.nth(self.start).unwrap_or(NIL),
+            end: arena.nth(self.end).unwrap_or(NIL),
+        }
+    }
+
+    fn extract_iter(&mut self, size: usize) -> Self::SequentialIterator {
+        let middle = self.end.min(self.start + size);
+        let front = ArenaParallelIterator {
+            arena: self.arena,
+            start: self.start,
+            end: middle,
+        };
+        self.start = middle;
+        front.to_sequential()
+    }
+}
+
+#[cfg(test)]
+mod arena_tests {
+    use super::*;
+
+    fn check_links(tree: &ArenaABR<u32, u32>) {
+        // every node is reachable exactly once, in order
+        let keys: Vec<u32> = tree.iter().map(|n| n.key).collect();
+        assert_eq!(keys.len(), tree.len());
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        for (index, node) in tree.nodes.iter().enumerate() {
+            for &child in &node.children {
+                assert!(child == NIL || tree.nodes[child as usize].parent == index as NodeIndex);
+            }
+        }
+        assert!(tree.root == NIL || tree.nodes[tree.root as usize].parent == NIL);
+        for node in &tree.nodes {
+            let size = |child: NodeIndex| match child {
+                NIL => 0,
+                child => tree.nodes[child as usize].size,
+            };
+            assert_eq!(
+                node.size,
+                1 + size(node.children[0]) + size(node.children[1])
+            );
+        }
+        for (position, key) in keys.iter().enumerate() {
+            assert_eq!(tree.nodes[tree.nth(position).unwrap() as usize].key, *key);
+        }
+        assert!(tree.nth(keys.len()).is_none());
+    }
+
+    #[test]
+    fn insert_get_remove() {
+        let mut tree = ArenaABR::new();
+        let mut x: u64 = 3;
+        let mut reference = std::collections::BTreeMap::new();
+        for _ in 0..2000 {
+            x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            let key = ((x >> 33) % 300) as u32;
+            if x & 0b100 == 0 {
+                assert_eq!(tree.remove(&key), reference.remove(&key));
+            } else {
+                assert_eq!(tree.insert(key, key * 2), reference.insert(key, key * 2));
+            }
+        }
+        check_links(&tree);
+        assert!(tree.iter().map(|n| n.key).eq(reference.keys().cloned()));
+        for key in 0..300 {
+            assert_eq!(tree.get(&key), reference.get(&key));
+        }
+    }
+
+    #[test]
+    fn remove_root_with_two_children() {
+        let mut tree = ArenaABR::new();
+        for &key in &[50, 30, 70, 60, 80, 65] {
+            tree.insert(key, key);
+        }
+        assert_eq!(tree.remove(&50), Some(50));
+        assert_eq!(tree.remove(&70), Some(70));
+        check_links(&tree);
+        assert!(tree.iter().map(|n| n.key).eq(vec![30, 60, 65, 80]));
+    }
+
+    #[test]
+    fn walk_backwards() {
+        let tree: ArenaABR<u32, ()> = vec![8, 4, 12, 2, 6, 10, 14, 1, 3, 5, 7]
+            .into_iter()
+            .collect();
+        let mut keys = Vec::new();
+        let mut current = tree.last();
+        while let Some(index) = current {
+            keys.push(tree.nodes[index as usize].key);
+            current = tree.predecessor(index);
+        }
+        assert_eq!(keys, vec![14, 12, 10, 8, 7, 6, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn empty() {
+        let mut tree: ArenaABR<u32, u32> = ArenaABR::new();
+        assert_eq!(tree.iter().count(), 0);
+        assert!(tree.first().is_none());
+        assert!(tree.remove(&1).is_none());
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt::Debug;
+use std::ops::Add;
+
+/// A summary maintained by the tree in each of its nodes
+///
+/// The summary of a node is the combination, in order, of the summary
+/// of its left subtree, of the node itself, and of its right subtree.
+/// It is kept up to date on insertions and removals, which allows
+/// aggregating any range of the tree in logarithmic time
+/// (see [ABR::aggregate]{struct.ABR.html#method.aggregate}).
+///
+/// Summaries must form a monoid : `combine` has to be associative,
+/// and `identity` (the summary of an empty subtree) its neutral element.
+/// `combine` does not need to be commutative.
+///
+/// # Examples
+/// Counting the entries whose value is even :
+///
+/// ```
+/// use abr::abr::ABR;
+/// use abr::augment::Augment;
+///
+/// #[derive(Debug)]
+/// struct Even;
+///
+/// impl<K> Augment<K, u32> for Even {
+///     type Summary = usize;
+///
+///     fn identity() -> usize {
+///         0
+///     }
+///
+///     fn summarize(_key: &K, value: &u32) -> usize {
+///         (value % 2 == 0) as usize
+///     }
+///
+///     fn combine(left: &usize, right: &usize) -> usize {
+///         left + right
+///     }
+/// }
+///
+/// let mut tree: ABR<_, _, Even> = ABR::default();
+/// for i in 0..10 {
+///     tree.insert(i, i * 3);
+/// }
+/// assert_eq!(tree.aggregate(..), 5);
+/// assert_eq!(tree.aggregate(2..5), 2);
+/// ```
+pub trait Augment<K, V> {
+    /// The type of the summary stored in the nodes.
+    type Summary: Clone + Debug;
+
+    /// Returns the summary of an empty subtree.
+    fn identity() -> Self::Summary;
+
+    /// Returns the summary of a single key/value pair.
+    fn summarize(key: &K, value: &V) -> Self::Summary;
+
+    /// Combines the summaries of two consecutive parts of the tree.
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary;
+}
+
+/// No augmentation : nothing is maintained in the nodes.
+impl<K, V> Augment<K, V> for () {
+    type Summary = ();
+
+    fn identity() {}
+
+    fn summarize(_key: &K, _value: &V) {}
+
+    fn combine(_left: &(), _right: &()) {}
+}
+
+/// Counts the entries of a subtree.
+#[derive(Debug)]
+pub struct Count;
+
+impl<K, V> Augment<K, V> for Count {
+    type Summary = usize;
+
+    fn identity() -> usize {
+        0
+    }
+
+    fn summarize(_key: &K, _value: &V) -> usize {
+        1
+    }
+
+    fn combine(left: &usize, right: &usize) -> usize {
+        left + right
+    }
+}
+
+/// Sums the values of a subtree.
+///
+/// The sum of an empty subtree is the default value of `V`.
+#[derive(Debug)]
+pub struct Sum;
+
+impl<K, V> Augment<K, V> for Sum
+where
+    V: Add<Output = V> + Clone + Debug + Default,
+{
+    type Summary = V;
+
+    fn identity() -> V {
+        V::default()
+    }
+
+    fn summarize(_key: &K, value: &V) -> V {
+        value.clone()
+    }
+
+    fn combine(left: &V, right: &V) -> V {
+        left.clone() + right.clone()
+    }
+}
+
+/// Keeps the minimum value of a subtree, `None` if it is empty.
+#[derive(Debug)]
+pub struct Min;
+
+impl<K, V> Augment<K, V> for Min
+where
+    V: Ord + Clone + Debug,
+{
+    type Summary = Option<V>;
+
+    fn identity() -> Option<V> {
+        None
+    }
+
+    fn summarize(_key: &K, value: &V) -> Option<V> {
+        Some(value.clone())
+    }
+
+    fn combine(left: &Option<V>, right: &Option<V>) -> Option<V> {
+        match (left, right) {
+            (Some(l), Some(r)) => Some(std::cmp::min(l, r).clone()),
+            (l, r) => l.as_ref().or(r.as_ref()).cloned(),
+        }
+    }
+}
+
+/// Keeps the maximum value of a subtree, `None` if it is empty.
+#[derive(Debug)]
+pub struct Max;
+
+impl<K, V> Augment<K, V> for Max
+where
+    V: Ord + Clone + Debug,
+{
+    type Summary = Option<V>;
+
+    fn identity() -> Option<V> {
+        None
+    }
+
+    fn summarize(_key: &K, value: &V) -> Option<V> {
+        Some(value.clone())
+    }
+
+    fn combine(left: &Option<V>, right: &Option<V>) -> Option<V> {
+        match (left, right) {
+            (Some(l), Some(r)) => Some(std::cmp::max(l, r).clone()),
+            (l, r) => l.as_ref().or(r.as_ref()).cloned(),
+        }
+    }
+}
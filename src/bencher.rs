@@ -1,4 +1,3 @@
-use crate::abr::ABR;
 use std::fs::File;
 use std::io::{BufWriter, Error, Write};
 use std::path::Path;
@@ -7,17 +6,18 @@ use time::precise_time_ns;
 
 const ITERATIONS: usize = 100;
 
+/// Measures the time taken by `function` on the trees built by `setup`.
+///
+/// The tree type is free : `setup` builds a tree of a given size, which is
+/// given to `function` along with an optional level. The tree is returned
+/// by `function` so that its drop is not measured.
 pub struct Bencher<'a, S, F> {
     path: &'a Path,
     setup: S,
     function: F,
 }
 
-impl<'a, S, F> Bencher<'a, S, F>
-where
-    S: Fn(usize) -> ABR<u64, ()>,
-    F: Fn(ABR<u64, ()>, Option<usize>) -> ABR<u64, ()>,
-{
+impl<'a, S, F> Bencher<'a, S, F> {
     pub fn new(path: &'a Path, setup: S, function: F) -> Bencher<'a, S, F> {
         std::fs::create_dir_all(path).expect("Could not create benchmark directory");
         Bencher {
@@ -27,7 +27,11 @@ where
         }
     }
 
-    fn bench(&self, size: usize, levels: Option<usize>) -> Duration {
+    fn bench<T>(&self, size: usize, levels: Option<usize>) -> Duration
+    where
+        S: Fn(usize) -> T,
+        F: Fn(T, Option<usize>) -> T,
+    {
         let mut results: Vec<u64> = vec![];
 
         for i in 0..ITERATIONS {
@@ -74,12 +78,16 @@ where
         Ok(())
     }
 
-    pub fn run_benchmark(
+    pub fn run_benchmark<T>(
         &self,
         name: &str,
         sizes: Vec<usize>,
         levels: Vec<Option<usize>>,
-    ) -> Result<(), Error> {
+    ) -> Result<(), Error>
+    where
+        S: Fn(usize) -> T,
+        F: Fn(T, Option<usize>) -> T,
+    {
         println!("Running {}...", name);
 
         for size in &sizes {
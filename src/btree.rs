@@ -0,0 +1,608 @@
+#[cfg(feature = "rayon_adaptive")]
+use crate::abr_parallel_iterator::find_any;
+#[cfg(feature = "rayon_adaptive")]
+use rayon_adaptive::prelude::*;
+#[cfg(feature = "rayon_adaptive")]
+use rayon_adaptive::BasicPower;
+use std::borrow::Borrow;
+
+/// The default maximum number of keys in a node.
+pub const DEFAULT_CAPACITY: usize = 11;
+
+/// A node of a [BTree]{struct.BTree.html}
+///
+/// Keys are sorted, and the subtree `children[i]` holds the keys lying
+/// between `keys[i - 1]` and `keys[i]`. Leaves have no children, and
+/// each node keeps the number of entries in its subtree.
+#[derive(Debug, Clone)]
+pub struct BTreeNode<K, V> {
+    pub keys: Vec<K>,
+    pub values: Vec<V>,
+    pub children: Vec<BTreeNode<K, V>>,
+    pub size: usize,
+}
+
+impl<K, V> BTreeNode<K, V> {
+    fn new() -> BTreeNode<K, V> {
+        BTreeNode {
+            keys: Vec::new(),
+            values: Vec::new(),
+            children: Vec::new(),
+            size: 0,
+        }
+    }
+
+    /// Returns `true` if the node has no children.
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Recomputes the size of the node from its keys and children.
+    fn update(&mut self) {
+        self.size = self.keys.len() + self.children.iter().map(|c| c.size).sum::<usize>();
+    }
+
+    /// Splits an overflowing node, keeping the first half of its keys.
+    ///
+    /// The median entry and the node holding the second half are returned.
+    fn split(&mut self) -> (K, V, BTreeNode<K, V>) {
+        let middle = self.keys.len() / 2;
+        let mut right = BTreeNode {
+            keys: self.keys.split_off(middle + 1),
+            values: self.values.split_off(middle + 1),
+            children: if self.is_leaf() {
+                Vec::new()
+            } else {
+                self.children.split_off(middle + 1)
+            },
+            size: 0,
+        };
+        let key = self.keys.pop().unwrap();
+        let value = self.values.pop().unwrap();
+        right.update();
+        self.update();
+        (key, value, right)
+    }
+}
+
+impl<K, V> BTreeNode<K, V>
+where
+    K: Ord,
+{
+    /// Inserts an entry in the subtree, returning the previous value of
+    /// the key, and the split half of the node if it overflowed.
+    #[allow(clippy::type_complexity)]
+    fn insert(
+        &mut self,
+        key: K,
+        value: V,
+        capacity: usize,
+    ) -> (Option<V>, Option<(K, V, BTreeNode<K, V>)>) {
+        match self.keys.binary_search(&key) {
+            Ok(index) => {
+                return (
+                    Some(std::mem::replace(&mut self.values[index], value)),
+                    None,
+                )
+            }
+            Err(index) => {
+                if self.is_leaf() {
+                    self.keys.insert(index, key);
+                    self.values.insert(index, value);
+                } else {
+                    let (old_value, split) = self.children[index].insert(key, value, capacity);
+                    if old_value.is_some() {
+                        return (old_value, None);
+                    }
+                    if let Some((key, value, right)) = split {
+                        self.keys.insert(index, key);
+                        self.values.insert(index, value);
+                        self.children.insert(index + 1, right);
+                    }
+                }
+            }
+        }
+
+        self.size += 1;
+        if self.keys.len() > capacity {
+            (None, Some(self.split()))
+        } else {
+            (None, None)
+        }
+    }
+
+    /// Removes a key from the subtree, leaving the children with at least
+    /// `minimum` keys.
+    fn remove<Q>(&mut self, key: &Q, minimum: usize) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let value = match self.keys.binary_search_by(|k| k.borrow().cmp(key)) {
+            Ok(index) if self.is_leaf() => {
+                self.keys.remove(index);
+                self.values.remove(index)
+            }
+            Ok(index) => {
+                // the greatest entry of the left subtree takes its place
+                let (key, value) = self.children[index].remove_max(minimum);
+                self.keys[index] = key;
+                let old_value = std::mem::replace(&mut self.values[index], value);
+                self.rebalance(index, minimum);
+                old_value
+            }
+            Err(_) if self.is_leaf() => return None,
+            Err(index) => {
+                let value = self.children[index].remove(key, minimum)?;
+                self.rebalance(index, minimum);
+                value
+            }
+        };
+        self.size -= 1;
+        Some(value)
+    }
+
+    /// Removes the greatest entry of the subtree.
+    fn remove_max(&mut self, minimum: usize) -> (K, V) {
+        self.size -= 1;
+        if self.is_leaf() {
+            (self.keys.pop().unwrap(), self.values.pop().unwrap())
+        } else {
+            let last = self.children.len() - 1;
+            let entry = self.children[last].remove_max(minimum);
+            self.rebalance(last, minimum);
+            entry
+        }
+    }
+
+    /// Brings back the child at `index` to at least `minimum` keys, by
+    /// taking a key from a sibling or merging with it.
+    fn rebalance(&mut self, index: usize, minimum: usize) {
+        if self.children[index].keys.len() >= minimum {
+            return;
+        }
+
+        if index > 0 && self.children[index - 1].keys.len() > minimum {
+            // rotate through the separator from the left sibling
+            let (left, right) = self.children.split_at_mut(index);
+            let (left, child) = (&mut left[index - 1], &mut right[0]);
+            let key = std::mem::replace(&mut self.keys[index - 1], left.keys.pop().unwrap());
+            let value = std::mem::replace(&mut self.values[index - 1], left.values.pop().unwrap());
+            child.keys.insert(0, key);
+            child.values.insert(0, value);
+            if let Some(moved) = left.children.pop() {
+                child.children.insert(0, moved);
+            }
+            left.update();
+            child.update();
+        } else if index + 1 < self.children.len() && self.children[index + 1].keys.len() > minimum {
+            // rotate through the separator from the right sibling
+            let (left, right) = self.children.split_at_mut(index + 1);
+            let (child, right) = (&mut left[index], &mut right[0]);
+            let key = std::mem::replace(&mut self.keys[index], right.keys.remove(0));
+            let value = std::mem::replace(&mut self.values[index], right.values.remove(0));
+            child.keys.push(key);
+            child.values.push(value);
+            if !right.is_leaf() {
+                child.children.push(right.children.remove(0));
+            }
+            right.update();
+            child.update();
+        } else {
+            // merge with a sibling and the separator
+            let index = if index > 0 { index - 1 } else { index };
+            let right = self.children.remove(index + 1);
+            let left = &mut self.children[index];
+            left.keys.push(self.keys.remove(index));
+            left.values.push(self.values.remove(index));
+            left.keys.extend(right.keys);
+            left.values.extend(right.values);
+            left.children.extend(right.children);
+            left.size += 1 + right.size;
+        }
+    }
+}
+
+/// A B-tree map
+///
+/// Each node holds up to `capacity` sorted keys, and all the leaves are at
+/// the same depth. Wide nodes make the tree shallow and keep neighbouring
+/// keys together in memory, binary search trees being the other extreme.
+///
+/// # Examples
+/// Basic usage :
+///
+/// ```
+/// use abr::btree::BTree;
+///
+/// let mut tree = BTree::with_capacity(4);
+/// for key in (0..100).rev() {
+///     tree.insert(key, key * 2);
+/// }
+///
+/// assert_eq!(tree.get(&21), Some(&42));
+/// assert_eq!(tree.remove(&21), Some(42));
+/// assert!(tree.iter().map(|(k, _)| *k).eq((0..100).filter(|k| *k != 21)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct BTree<K, V> {
+    pub root: BTreeNode<K, V>,
+    capacity: usize,
+}
+
+impl<K, V> Default for BTree<K, V> {
+    fn default() -> Self {
+        BTree {
+            root: BTreeNode::new(),
+            capacity: DEFAULT_CAPACITY,
+        }
+    }
+}
+
+/// Enables collection into a B-tree, as for `ABR`.
+impl<K> std::iter::FromIterator<K> for BTree<K, ()>
+where
+    K: Ord,
+{
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = K>,
+    {
+        let mut tree = BTree::new();
+        for key in iter {
+            tree.insert(key, ());
+        }
+        tree
+    }
+}
+
+impl<K, V> BTree<K, V>
+where
+    K: Ord,
+{
+    /// Create a new, empty B-tree, with nodes of `DEFAULT_CAPACITY` keys.
+    pub fn new() -> BTree<K, V> {
+        BTree::default()
+    }
+
+    /// Create a new, empty B-tree whose nodes hold up to `capacity` keys.
+    ///
+    /// # Panics
+    /// The function will panic if `capacity` is less than 2.
+    pub fn with_capacity(capacity: usize) -> BTree<K, V> {
+        assert!(capacity >= 2, "nodes must hold at least 2 keys");
+        BTree {
+            root: BTreeNode::new(),
+            capacity,
+        }
+    }
+
+    /// Returns the maximum number of keys in a node.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of entries in the tree.
+    pub fn len(&self) -> usize {
+        self.root.size
+    }
+
+    /// Returns `true` if the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.root.size == 0
+    }
+
+    /// Inserts a key and value pair in the tree
+    ///
+    /// If the key was already present, its previous value is returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let (old_value, split) = self.root.insert(key, value, self.capacity);
+        if let Some((key, value, right)) = split {
+            let left = std::mem::replace(&mut self.root, BTreeNode::new());
+            self.root = BTreeNode {
+                keys: vec![key],
+                values: vec![value],
+                size: left.size + right.size + 1,
+                children: vec![left, right],
+            };
+        }
+        old_value
+    }
+
+    /// Returns the value associated to a key, if present.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut node = &self.root;
+        loop {
+            match node.keys.binary_search_by(|k| k.borrow().cmp(key)) {
+                Ok(index) => return Some(&node.values[index]),
+                Err(_) if node.is_leaf() => return None,
+                Err(index) => node = &node.children[index],
+            }
+        }
+    }
+
+    /// Returns `true` if the specified key is contained in the tree.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Removes a key from the tree, returning its value if present.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let value = self.root.remove(key, self.capacity / 2)?;
+        if self.root.keys.is_empty() && !self.root.is_leaf() {
+            self.root = self.root.children.pop().unwrap();
+        }
+        Some(value)
+    }
+
+    /// Returns an in-order iterator over the entries of the tree.
+    pub fn iter(&self) -> BTreeIterator<'_, K, V> {
+        BTreeIterator {
+            stack: vec![Piece::Subtree(&self.root)],
+            remaining: self.root.size,
+        }
+    }
+
+    /// Get a parallel iterator (using rayon_adaptive) from the tree.
+    ///
+    /// The entries are divided exactly where asked, using the sizes
+    /// of the subtrees.
+    /// ```
+    /// use abr::btree::BTree;
+    /// use rayon_adaptive::prelude::*;
+    ///
+    /// let tree: BTree<_, _> = (1..=100).collect();
+    /// assert_eq!(tree.par_iter().map(|(k, _)| *k).reduce(|| 0, |a, b| a + b), 5050);
+    /// ```
+    #[cfg(feature = "rayon_adaptive")]
+    pub fn par_iter(&self) -> BTreeParallelIterator<'_, K, V> {
+        BTreeParallelIterator {
+            iterator: self.iter(),
+        }
+    }
+
+    /// Searches in parallel (using rayon_adaptive) for an entry matching
+    /// `predicate`, returning any of them.
+    ///
+    /// All the tasks stop as soon as one of them found a match.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::btree::BTree;
+    ///
+    /// let tree: BTree<u32, ()> = (0..1000).collect();
+    /// let (key, _) = tree.par_find_any(|k, _| k % 100 == 42).unwrap();
+    /// assert_eq!(key % 100, 42);
+    /// assert!(tree.par_find_any(|k, _| *k > 1000).is_none());
+    /// ```
+    #[cfg(feature = "rayon_adaptive")]
+    pub fn par_find_any<P>(&self, predicate: P) -> Option<(&K, &V)>
+    where
+        K: Sync,
+        V: Sync,
+        P: Fn(&K, &V) -> bool + Sync,
+    {
+        find_any(self.par_iter(), &|&(key, value): &(&K, &V)| {
+            predicate(key, value)
+        })
+    }
+}
+
+/// A part of the entries left to iterate on : a single entry or a
+/// whole subtree.
+#[derive(Debug)]
+enum Piece<'a, K, V> {
+    Entry(&'a K, &'a V),
+    Subtree(&'a BTreeNode<K, V>),
+}
+
+#[cfg(feature = "rayon_adaptive")]
+impl<'a, K, V> Piece<'a, K, V> {
+    fn len(&self) -> usize {
+        match self {
+            Piece::Entry(..) => 1,
+            Piece::Subtree(node) => node.size,
+        }
+    }
+}
+
+/// A sequential iterator for the [BTree]{struct.BTree.html} structure.
+///
+/// The pieces left to iterate on are kept on a stack, the next one on top.
+#[derive(Debug)]
+pub struct BTreeIterator<'a, K, V> {
+    stack: Vec<Piece<'a, K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> BTreeIterator<'a, K, V> {
+    /// Pushes the entries and children of a node, the first one on top.
+    fn open(&mut self, node: &'a BTreeNode<K, V>) {
+        let mut children = node.children.iter().rev();
+        if let Some(child) = children.next() {
+            self.stack.push(Piece::Subtree(child));
+        }
+        for ((key, value), child) in node.keys.iter().zip(&node.values).rev().zip(children) {
+            self.stack.push(Piece::Entry(key, value));
+            self.stack.push(Piece::Subtree(child));
+        }
+        if node.is_leaf() {
+            for (key, value) in node.keys.iter().zip(&node.values).rev() {
+                self.stack.push(Piece::Entry(key, value));
+            }
+        }
+    }
+
+    /// Splits off the first `index` entries (or all of them if there are
+    /// fewer) into a new iterator.
+    #[cfg(feature = "rayon_adaptive")]
+    fn split_off_front(&mut self, index: usize) -> BTreeIterator<'a, K, V> {
+        let index = index.min(self.remaining);
+        let mut front = Vec::new();
+        let mut taken = 0;
+        while taken < index {
+            let piece = self.stack.pop().unwrap();
+            if taken + piece.len() <= index {
+                taken += piece.len();
+                front.push(piece);
+            } else if let Piece::Subtree(node) = piece {
+                self.open(node);
+            }
+        }
+        front.reverse();
+        self.remaining -= index;
+        BTreeIterator {
+            stack: front,
+            remaining: index,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for BTreeIterator<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.pop()? {
+                Piece::Entry(key, value) => {
+                    self.remaining -= 1;
+                    return Some((key, value));
+                }
+                Piece::Subtree(node) => self.open(node),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for BTreeIterator<'a, K, V> {}
+
+/// A parallel iterator (using rayon_adaptive) for the
+/// [BTree]{struct.BTree.html} structure.
+#[cfg(feature = "rayon_adaptive")]
+#[derive(Debug)]
+pub struct BTreeParallelIterator<'a, K, V> {
+    iterator: BTreeIterator<'a, K, V>,
+}
+
+#[cfg(feature = "rayon_adaptive")]
+impl<'a, K, V> Divisible for BTreeParallelIterator<'a, K, V> {
+    type Power = BasicPower;
+
+    fn base_length(&self) -> Option<usize> {
+        Some(self.iterator.remaining)
+    }
+
+    fn divide_at(mut self, index: usize) -> (Self, Self) {
+        let front = self.iterator.split_off_front(index);
+        (BTreeParallelIterator { iterator: front }, self)
+    }
+}
+
+#[cfg(feature = "rayon_adaptive")]
+impl<'a, K, V> ParallelIterator for BTreeParallelIterator<'a, K, V>
+where
+    K: Sync,
+    V: Sync,
+{
+    type Item = (&'a K, &'a V);
+
+    type SequentialIterator = BTreeIterator<'a, K, V>;
+
+    fn to_sequential(self) -> Self::SequentialIterator {
+        self.iterator
+    }
+
+    fn extract_iter(&mut self, size: usize) -> Self::SequentialIterator {
+        self.iterator.split_off_front(size)
+    }
+}
+
+#[cfg(test)]
+mod btree_tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    /// Checks the sizes, key counts and leaf depths of a subtree,
+    /// returning its height.
+    fn check_node(node: &BTreeNode<u32, u32>, capacity: usize, is_root: bool) -> usize {
+        assert!(node.keys.len() <= capacity);
+        assert!(is_root || node.keys.len() >= capacity / 2);
+        assert_eq!(node.keys.len(), node.values.len());
+        let mut size = node.keys.len();
+        let mut heights = node.children.iter().map(|child| {
+            size += child.size;
+            check_node(child, capacity, false)
+        });
+        let height = heights.next().unwrap_or(0);
+        assert!(heights.all(|h| h == height));
+        assert!(node.is_leaf() || node.children.len() == node.keys.len() + 1);
+        assert_eq!(node.size, size);
+        height + 1
+    }
+
+    #[test]
+    fn matches_btreemap() {
+        for &capacity in &[2, 3, 4, 7, 11] {
+            let mut tree = BTree::with_capacity(capacity);
+            let mut reference = BTreeMap::new();
+            let mut x: u64 = capacity as u64;
+            for _ in 0..3000 {
+                x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                let key = ((x >> 33) % 500) as u32;
+                if x & 0b110 == 0 {
+                    assert_eq!(tree.remove(&key), reference.remove(&key));
+                } else {
+                    assert_eq!(tree.insert(key, key + 1), reference.insert(key, key + 1));
+                }
+                check_node(&tree.root, capacity, true);
+            }
+            assert_eq!(tree.len(), reference.len());
+            assert!(tree.iter().eq(reference.iter()));
+            for key in 0..500 {
+                assert_eq!(tree.get(&key), reference.get(&key));
+            }
+            for key in 0..500 {
+                assert_eq!(tree.remove(&key), reference.remove(&key));
+            }
+            assert!(tree.is_empty());
+        }
+    }
+
+    #[cfg(feature = "rayon_adaptive")]
+    #[test]
+    fn split_off_front() {
+        let tree: BTree<u32, ()> = {
+            let mut tree = BTree::with_capacity(3);
+            for key in 0..200 {
+                tree.insert(key, ());
+            }
+            tree
+        };
+        for &index in &[0, 1, 7, 100, 199, 200, 250] {
+            let mut back = tree.iter();
+            let front = back.split_off_front(index);
+            let index = index.min(200);
+            assert_eq!((front.len(), back.len()), (index, 200 - index));
+            assert!(front.map(|(k, _)| *k).eq(0..index as u32));
+            assert!(back.map(|(k, _)| *k).eq(index as u32..200));
+        }
+    }
+}
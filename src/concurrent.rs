@@ -0,0 +1,697 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex, RwLock};
+
+pub type Link<K, V> = RwLock<Option<Arc<ConcurrentNode<K, V>>>>;
+
+type SharedNode<K, V> = Arc<ConcurrentNode<K, V>>;
+
+/// A node in a concurrent binary search tree
+///
+/// The key of a node never changes. Its values and each of its children
+/// are protected by their own lock, so that threads working on different
+/// parts of the tree never wait for each other.
+///
+/// The values are kept along with the version of the tree which wrote
+/// them (`None` marking a removal), the latest one last. Older values are
+/// only kept while a snapshot may still need them.
+#[derive(Debug)]
+pub struct ConcurrentNode<K, V> {
+    pub key: K,
+    pub versions: RwLock<Vec<(u64, Option<V>)>>,
+    pub children: [Link<K, V>; 2],
+    // set once the node is taken out of the tree
+    unlinked: AtomicBool,
+}
+
+impl<K, V> ConcurrentNode<K, V> {
+    fn new(key: K, value: V, version: u64) -> ConcurrentNode<K, V> {
+        ConcurrentNode {
+            key,
+            versions: RwLock::new(vec![(version, Some(value))]),
+            children: [RwLock::new(None), RwLock::new(None)],
+            unlinked: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Returns `true` if the latest of a node's versions is a removal.
+fn is_removed<V>(versions: &[(u64, Option<V>)]) -> bool {
+    versions.last().map_or(true, |(_, value)| value.is_none())
+}
+
+/// Returns the value of a node seen by the snapshot of the given version.
+fn value_at<V>(versions: &[(u64, Option<V>)], snapshot: u64) -> Option<&V> {
+    versions
+        .iter()
+        .rev()
+        .find(|(version, _)| *version <= snapshot)
+        .and_then(|(_, value)| value.as_ref())
+}
+
+/// A binary search tree which can be modified from several threads at once
+///
+/// `insert`, `get` and `remove` only take `&self`, and lock at most one
+/// link or value at a time : the path to a key is followed by cloning
+/// the reference to each child under its link's read lock. Removed keys
+/// are first marked as such, then their node is taken out of the tree once
+/// it is a leaf and no snapshot may still see it : this locks the link to
+/// the node, then its value and its children.
+///
+/// Each modification gets a new version of the tree from a global counter,
+/// without any global lock. A [snapshot](#method.snapshot) reads the tree
+/// at the current version, ignoring the values written after it. The
+/// versions of the live snapshots are registered : a value is kept as long
+/// as one of them sees it.
+///
+/// The tree is not balanced : keys inserted in order make it degenerate
+/// into a list, and every operation linear.
+///
+/// # Examples
+/// Basic usage :
+///
+/// ```
+/// use abr::concurrent::ConcurrentABR;
+/// use std::sync::Arc;
+///
+/// let tree = Arc::new(ConcurrentABR::new());
+/// let writers: Vec<_> = (0..4)
+///     .map(|t| {
+///         let tree = tree.clone();
+///         std::thread::spawn(move || {
+///             for i in 0..100 {
+///                 tree.insert(i * 4 + t, t);
+///             }
+///         })
+///     })
+///     .collect();
+/// for writer in writers {
+///     writer.join().unwrap();
+/// }
+///
+/// assert_eq!(tree.len(), 400);
+/// assert!(tree.iter().map(|(k, _)| k).eq(0..400));
+/// ```
+#[derive(Debug)]
+pub struct ConcurrentABR<K, V> {
+    root: Link<K, V>,
+    length: AtomicUsize,
+    // version of the last modification
+    clock: AtomicU64,
+    // versions of the live snapshots
+    snapshots: Mutex<Vec<u64>>,
+}
+
+impl<K, V> Default for ConcurrentABR<K, V>
+where
+    K: Ord,
+{
+    fn default() -> Self {
+        ConcurrentABR::new()
+    }
+}
+
+impl<K, V> ConcurrentABR<K, V>
+where
+    K: Ord,
+{
+    /// Create a new, empty concurrent tree.
+    pub fn new() -> ConcurrentABR<K, V> {
+        ConcurrentABR {
+            root: RwLock::new(None),
+            length: AtomicUsize::new(0),
+            clock: AtomicU64::new(0),
+            snapshots: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns the number of keys in the tree.
+    pub fn len(&self) -> usize {
+        self.length.load(AtomicOrdering::SeqCst)
+    }
+
+    /// Returns `true` if the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a new version number.
+    fn next_version(&self) -> u64 {
+        self.clock.fetch_add(1, AtomicOrdering::SeqCst) + 1
+    }
+
+    /// Returns `true` if a live snapshot sees one of the given values.
+    ///
+    /// The snapshots taken from now on only see the latest one : their
+    /// version is read from the clock under the same lock.
+    fn seen_by_snapshot(&self, versions: &[(u64, Option<V>)]) -> bool {
+        let snapshots = self.snapshots.lock().unwrap();
+        snapshots
+            .iter()
+            .any(|snapshot| value_at(versions, *snapshot).is_some())
+    }
+
+    /// Writes a new value (or removal) of a key in its node's versions,
+    /// which must be locked. Returns the previous value.
+    ///
+    /// Only the latest value is kept, and the ones live snapshots see.
+    fn write_version(&self, versions: &mut Vec<(u64, Option<V>)>, value: Option<V>) -> Option<V>
+    where
+        V: Clone,
+    {
+        versions.push((self.next_version(), value));
+        let seen: Vec<bool> = {
+            let snapshots = self.snapshots.lock().unwrap();
+            versions
+                .windows(2)
+                .map(|pair| {
+                    snapshots
+                        .iter()
+                        .any(|snapshot| pair[0].0 <= *snapshot && *snapshot < pair[1].0)
+                })
+                .collect()
+        };
+        let previous = seen.len() - 1;
+        let previous = if seen[previous] {
+            versions[previous].1.clone()
+        } else {
+            versions[previous].1.take()
+        };
+        let mut seen = seen.into_iter().chain(Some(true));
+        versions.retain(|_| seen.next().unwrap());
+        previous
+    }
+
+    /// Finds the node holding a key, present or removed.
+    fn find<Q>(&self, key: &Q) -> Option<Arc<ConcurrentNode<K, V>>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find_path(key).pop()
+    }
+
+    /// Returns the path from the root to the node holding a key, this
+    /// node included if found.
+    fn find_path<Q>(&self, key: &Q) -> Vec<Arc<ConcurrentNode<K, V>>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut path = Vec::new();
+        let mut current = self.root.read().unwrap().clone();
+        while let Some(node) = current {
+            let direction = match key.cmp(node.key.borrow()) {
+                Ordering::Equal => {
+                    path.push(node);
+                    return path;
+                }
+                Ordering::Less => 0,
+                Ordering::Greater => 1,
+            };
+            current = node.children[direction].read().unwrap().clone();
+            path.push(node);
+        }
+        Vec::new()
+    }
+
+    /// Inserts a key and value pair in the tree
+    ///
+    /// If the key was already present, its previous value is returned.
+    pub fn insert(&self, key: K, value: V) -> Option<V>
+    where
+        V: Clone,
+    {
+        let mut parent: Option<Arc<ConcurrentNode<K, V>>> = None;
+        let mut direction = 0;
+
+        loop {
+            let link = match &parent {
+                Some(node) => &node.children[direction],
+                None => &self.root,
+            };
+            let child = link.read().unwrap().clone();
+
+            match child {
+                Some(node) => {
+                    direction = match key.cmp(&node.key) {
+                        Ordering::Equal => {
+                            let mut versions = node.versions.write().unwrap();
+                            if node.unlinked.load(AtomicOrdering::SeqCst) {
+                                // the removed node left the tree meanwhile, start over
+                                parent = None;
+                                continue;
+                            }
+                            let old_value = self.write_version(&mut versions, Some(value));
+                            if old_value.is_none() {
+                                self.length.fetch_add(1, AtomicOrdering::SeqCst);
+                            }
+                            return old_value;
+                        }
+                        Ordering::Less => 0,
+                        Ordering::Greater => 1,
+                    };
+                    parent = Some(node);
+                }
+                None => {
+                    let mut slot = link.write().unwrap();
+                    let unlinked = parent
+                        .as_ref()
+                        .is_some_and(|node| node.unlinked.load(AtomicOrdering::SeqCst));
+                    if unlinked {
+                        // the parent left the tree meanwhile, start over
+                        drop(slot);
+                        parent = None;
+                        continue;
+                    }
+                    if slot.is_none() {
+                        let node = ConcurrentNode::new(key, value, self.next_version());
+                        *slot = Some(Arc::new(node));
+                        self.length.fetch_add(1, AtomicOrdering::SeqCst);
+                        return None;
+                    }
+                    // another thread filled the link meanwhile, look again
+                }
+            }
+        }
+    }
+
+    /// Returns a copy of the value associated to a key.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::concurrent::ConcurrentABR;
+    ///
+    /// let tree = ConcurrentABR::new();
+    /// tree.insert(String::from("hello"), 1);
+    ///
+    /// assert_eq!(tree.get("hello"), Some(1));
+    /// assert_eq!(tree.get("world"), None);
+    /// ```
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        V: Clone,
+    {
+        self.find(key).and_then(|node| {
+            let versions = node.versions.read().unwrap();
+            versions.last().and_then(|(_, value)| value.clone())
+        })
+    }
+
+    /// Returns `true` if the specified key is contained in the tree.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.find(key)
+            .map(|node| !is_removed(&node.versions.read().unwrap()))
+            .unwrap_or(false)
+    }
+
+    /// Removes a key from the tree, returning its value if present.
+    ///
+    /// The node of the key, and the removed ancestors it leaves as
+    /// leaves, are then taken out of the tree if no live snapshot sees
+    /// them.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::concurrent::ConcurrentABR;
+    ///
+    /// let tree = ConcurrentABR::new();
+    /// tree.insert(1, "hello");
+    ///
+    /// assert_eq!(tree.remove(&1), Some("hello"));
+    /// assert_eq!(tree.remove(&1), None);
+    /// assert!(tree.is_empty());
+    /// ```
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        V: Clone,
+    {
+        let mut path = self.find_path(key);
+        let removed = {
+            let node = path.last()?;
+            let mut versions = node.versions.write().unwrap();
+            if is_removed(&versions) {
+                return None;
+            }
+            self.write_version(&mut versions, None)
+        };
+        self.length.fetch_sub(1, AtomicOrdering::SeqCst);
+
+        while let Some(node) = path.pop() {
+            if !self.unlink(path.last(), &node) {
+                break;
+            }
+        }
+        removed
+    }
+
+    /// Takes a removed leaf out of the tree, if no live snapshot sees one of
+    /// its values. Returns `true` if the node was unlinked.
+    fn unlink(&self, parent: Option<&SharedNode<K, V>>, node: &SharedNode<K, V>) -> bool {
+        let link = match parent {
+            Some(parent) => &parent.children[(node.key > parent.key) as usize],
+            None => &self.root,
+        };
+        let mut slot = link.write().unwrap();
+        if !slot.as_ref().is_some_and(|child| Arc::ptr_eq(child, node)) {
+            return false;
+        }
+        // inserters check the flag under the versions or a child link
+        // write lock, which these read locks hold off
+        let versions = node.versions.read().unwrap();
+        let left = node.children[0].read().unwrap();
+        let right = node.children[1].read().unwrap();
+        if !is_removed(&versions) || left.is_some() || right.is_some() {
+            return false;
+        }
+        if self.seen_by_snapshot(&versions) {
+            return false;
+        }
+        node.unlinked.store(true, AtomicOrdering::SeqCst);
+        *slot = None;
+        true
+    }
+
+    /// Takes all the removed leaves out of the tree, children first so that
+    /// removed nodes left as leaves go too.
+    fn compact(&self) {
+        let mut nodes = Vec::new();
+        let mut stack = Vec::new();
+        stack.extend(self.root.read().unwrap().clone().map(|root| (None, root)));
+        while let Some((parent, node)) = stack.pop() {
+            for child in &node.children {
+                if let Some(child) = child.read().unwrap().clone() {
+                    stack.push((Some(node.clone()), child));
+                }
+            }
+            nodes.push((parent, node));
+        }
+        // every node comes after its ancestors
+        for (parent, node) in nodes.into_iter().rev() {
+            self.unlink(parent.as_ref(), &node);
+        }
+    }
+
+    /// Takes a snapshot of the tree at the current version.
+    ///
+    /// The values it sees are kept in the tree until it is dropped.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::concurrent::ConcurrentABR;
+    ///
+    /// let tree = ConcurrentABR::new();
+    /// tree.insert(1, "hello");
+    /// let snapshot = tree.snapshot();
+    /// tree.insert(1, "world");
+    /// tree.insert(2, "!");
+    ///
+    /// assert_eq!(snapshot.get(&1), Some("hello"));
+    /// assert!(snapshot.iter().eq(vec![(1, "hello")]));
+    /// assert_eq!(tree.get(&1), Some("world"));
+    /// ```
+    pub fn snapshot(&self) -> ConcurrentSnapshot<'_, K, V> {
+        let mut snapshots = self.snapshots.lock().unwrap();
+        let version = self.clock.load(AtomicOrdering::SeqCst);
+        snapshots.push(version);
+        ConcurrentSnapshot {
+            tree: self,
+            version,
+        }
+    }
+
+    /// Returns an iterator over a consistent snapshot of the tree, in order.
+    ///
+    /// The entries of the current version are copied without blocking the
+    /// modifications, the iterator itself does not hold any lock. The
+    /// removed nodes met on the way are then taken out of the tree.
+    pub fn iter(&self) -> std::vec::IntoIter<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let (entries, removed) = self.snapshot().entries();
+        if removed > 0 {
+            self.compact();
+        }
+        entries.into_iter()
+    }
+}
+
+/// A consistent view of a [ConcurrentABR]{struct.ConcurrentABR.html}, at
+/// the version it was taken.
+///
+/// The values it sees stay in the tree while it lives : it is
+/// unregistered when dropped.
+#[derive(Debug)]
+pub struct ConcurrentSnapshot<'a, K, V> {
+    tree: &'a ConcurrentABR<K, V>,
+    version: u64,
+}
+
+impl<'a, K, V> ConcurrentSnapshot<'a, K, V>
+where
+    K: Ord,
+{
+    /// Returns the version of the tree seen by the snapshot.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns a copy of the value associated to a key in the snapshot.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        V: Clone,
+    {
+        self.tree
+            .find(key)
+            .and_then(|node| value_at(&node.versions.read().unwrap(), self.version).cloned())
+    }
+
+    /// Returns an iterator over the entries of the snapshot, in order.
+    ///
+    /// The entries are copied without blocking the modifications.
+    pub fn iter(&self) -> std::vec::IntoIter<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.entries().0.into_iter()
+    }
+
+    /// Copies the entries of the snapshot, in order, also counting the
+    /// removed nodes met on the way.
+    fn entries(&self) -> (Vec<(K, V)>, usize)
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let mut entries = Vec::with_capacity(self.tree.len());
+        let mut removed = 0;
+        let mut stack: Vec<Arc<ConcurrentNode<K, V>>> = Vec::new();
+        let mut current = self.tree.root.read().unwrap().clone();
+
+        loop {
+            while let Some(node) = current {
+                current = node.children[0].read().unwrap().clone();
+                stack.push(node);
+            }
+            match stack.pop() {
+                Some(node) => {
+                    {
+                        let versions = node.versions.read().unwrap();
+                        match value_at(&versions, self.version) {
+                            Some(value) => entries.push((node.key.clone(), value.clone())),
+                            None => removed += is_removed(&versions) as usize,
+                        }
+                    }
+                    current = node.children[1].read().unwrap().clone();
+                }
+                None => break,
+            }
+        }
+        (entries, removed)
+    }
+}
+
+impl<'a, K, V> Drop for ConcurrentSnapshot<'a, K, V> {
+    fn drop(&mut self) {
+        let mut snapshots = self.tree.snapshots.lock().unwrap();
+        if let Some(position) = snapshots.iter().position(|v| *v == self.version) {
+            snapshots.swap_remove(position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod concurrent_tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn parallel_inserts_and_removes() {
+        let tree = Arc::new(ConcurrentABR::new());
+        let threads: Vec<_> = (0..4u64)
+            .map(|t| {
+                let tree = tree.clone();
+                thread::spawn(move || {
+                    for i in 0..500u64 {
+                        let key = (i * 7919 + t * 131) % 2000;
+                        tree.insert(key, t);
+                        if i % 3 == 0 {
+                            tree.remove(&((key + 1000) % 2000));
+                        }
+                        tree.get(&key);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let entries: Vec<(u64, u64)> = tree.iter().collect();
+        assert_eq!(entries.len(), tree.len());
+        assert!(entries.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(entries.iter().all(|(k, _)| tree.contains_key(k)));
+    }
+
+    #[test]
+    fn reinsert_removed_key() {
+        let tree = ConcurrentABR::new();
+        tree.insert(2, 'a');
+        tree.insert(1, 'b');
+        assert_eq!(tree.remove(&2), Some('a'));
+        assert!(!tree.contains_key(&2));
+        assert_eq!(tree.insert(2, 'c'), None);
+        assert_eq!(tree.get(&2), Some('c'));
+        assert_eq!(tree.len(), 2);
+        assert!(tree.iter().eq(vec![(1, 'b'), (2, 'c')]));
+    }
+
+    #[test]
+    fn snapshot_while_writing() {
+        let tree = Arc::new(ConcurrentABR::new());
+        for i in 0..100 {
+            tree.insert(i, i);
+        }
+        let writer = {
+            let tree = tree.clone();
+            thread::spawn(move || {
+                for i in 100..1000 {
+                    tree.insert(i, i);
+                }
+            })
+        };
+        for _ in 0..10 {
+            let snapshot: Vec<_> = tree.iter().map(|(k, _)| k).collect();
+            // the writer inserts in increasing order : a snapshot is a prefix
+            assert!(snapshot.iter().cloned().eq(0..snapshot.len()));
+        }
+        writer.join().unwrap();
+        assert_eq!(tree.len(), 1000);
+    }
+
+    #[test]
+    fn removed_nodes_are_unlinked() {
+        let tree = ConcurrentABR::new();
+        for &key in &[50, 25, 75, 10, 30, 60, 90] {
+            tree.insert(key, ());
+        }
+        // an inner node stays, until its children go
+        tree.remove(&25);
+        assert!(tree.find(&25).is_some());
+        tree.remove(&10);
+        assert!(tree.find(&10).is_none());
+        tree.remove(&30);
+        assert!(tree.find(&30).is_none() && tree.find(&25).is_none());
+
+        for &key in &[50, 75, 60, 90] {
+            tree.remove(&key);
+        }
+        assert!(tree.root.read().unwrap().is_none());
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn iter_compacts_removed_nodes() {
+        let tree = ConcurrentABR::new();
+        for key in &[2, 1, 3] {
+            tree.insert(*key, ());
+        }
+        // a live snapshot keeps the removed leaf in the tree
+        let snapshot = tree.snapshot();
+        tree.remove(&3);
+        assert!(tree.find(&3).is_some());
+        assert!(tree.iter().eq(vec![(1, ()), (2, ())]));
+        assert!(tree.find(&3).is_some());
+        assert!(snapshot.iter().eq(vec![(1, ()), (2, ()), (3, ())]));
+
+        std::mem::drop(snapshot);
+        assert!(tree.iter().eq(vec![(1, ()), (2, ())]));
+        assert!(tree.find(&3).is_none());
+    }
+
+    #[test]
+    fn unseen_nodes_are_unlinked() {
+        let tree = ConcurrentABR::new();
+        for key in &[2, 1, 3] {
+            tree.insert(*key, ());
+        }
+        tree.remove(&3);
+        let snapshot = tree.snapshot();
+        tree.insert(3, ());
+        tree.remove(&3);
+        // the snapshot was taken between the two lives of the key
+        assert_eq!(snapshot.get(&3), None);
+        assert!(tree.find(&3).is_none());
+    }
+
+    #[test]
+    fn old_versions_are_dropped() {
+        let versions = |tree: &ConcurrentABR<u32, char>| -> Vec<char> {
+            let node = tree.find(&1).unwrap();
+            let versions = node.versions.read().unwrap();
+            versions.iter().map(|(_, value)| value.unwrap()).collect()
+        };
+        let tree = ConcurrentABR::new();
+        tree.insert(1, 'a');
+        let first = tree.snapshot();
+        assert_eq!(tree.insert(1, 'b'), Some('a'));
+        assert_eq!(versions(&tree), vec!['a', 'b']);
+        // no snapshot sees 'b' once replaced
+        assert_eq!(tree.insert(1, 'c'), Some('b'));
+        assert_eq!(versions(&tree), vec!['a', 'c']);
+
+        let second = tree.snapshot();
+        assert_eq!(tree.insert(1, 'd'), Some('c'));
+        assert_eq!(versions(&tree), vec!['a', 'c', 'd']);
+        assert_eq!((first.get(&1), second.get(&1)), (Some('a'), Some('c')));
+        assert!(first.version() < second.version());
+
+        // the older snapshot goes, the newer one still sees 'c'
+        std::mem::drop(first);
+        assert_eq!(tree.insert(1, 'e'), Some('d'));
+        assert_eq!(versions(&tree), vec!['c', 'e']);
+        std::mem::drop(second);
+        assert_eq!(tree.insert(1, 'f'), Some('e'));
+        assert_eq!(versions(&tree), vec!['f']);
+    }
+}
@@ -0,0 +1,503 @@
+use crate::abr::ABR;
+use crate::augment::Augment;
+use crate::node::{Node, OptBoxedNode};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+/// A node on the path of a cursor, seen either through a shared reference
+/// or through a pointer into a mutably borrowed tree.
+trait PathNode<K>: Copy {
+    fn child(self, direction: usize) -> Option<Self>;
+
+    fn same(self, other: Self) -> bool;
+
+    /// Compares `key` to the key of the node.
+    fn compare<Q>(self, key: &Q) -> Ordering
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized;
+}
+
+impl<K, V, A> PathNode<K> for &Node<K, V, A>
+where
+    A: Augment<K, V>,
+{
+    fn child(self, direction: usize) -> Option<Self> {
+        self.children[direction].as_deref()
+    }
+
+    fn same(self, other: Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+
+    fn compare<Q>(self, key: &Q) -> Ordering
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        key.cmp(self.key.borrow())
+    }
+}
+
+// SAFETY: the pointers handled by a `CursorMut` all point to nodes of the
+// tree it borrows mutably, which are only reached through the cursor for
+// as long as it lives. Nodes are boxed : relinking them does not move them.
+impl<K, V, A> PathNode<K> for NonNull<Node<K, V, A>>
+where
+    A: Augment<K, V>,
+{
+    fn child(self, direction: usize) -> Option<Self> {
+        unsafe { (*self.as_ptr()).children[direction].as_deref_mut() }.map(NonNull::from)
+    }
+
+    fn same(self, other: Self) -> bool {
+        self == other
+    }
+
+    fn compare<Q>(self, key: &Q) -> Ordering
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        key.cmp(unsafe { &(*self.as_ptr()).key }.borrow())
+    }
+}
+
+/// Pushes the nodes from `start` down, always going in `direction`.
+fn descent<K, P: PathNode<K>>(path: &mut Vec<P>, mut start: Option<P>, direction: usize) {
+    while let Some(node) = start {
+        path.push(node);
+        start = node.child(direction);
+    }
+}
+
+/// Pops the top of the path, and its ancestors for as long as we come
+/// from their child in `direction`.
+fn climb<K, P: PathNode<K>>(path: &mut Vec<P>, direction: usize) {
+    while let Some(from) = path.pop() {
+        match path.last() {
+            Some(parent) if matches!(parent.child(direction), Some(c) if c.same(from)) => {}
+            _ => break,
+        }
+    }
+}
+
+/// Moves the top of the path to its neighbour in `direction` : 1 for the
+/// next node, 0 for the previous one. An empty path stands for the ghost
+/// position, from which we move to the first or last node.
+fn step<K, P: PathNode<K>>(path: &mut Vec<P>, root: Option<P>, direction: usize) {
+    match path.last() {
+        None => descent(path, root, 1 - direction),
+        Some(node) => match node.child(direction) {
+            Some(child) => descent(path, Some(child), 1 - direction),
+            None => climb(path, direction),
+        },
+    }
+}
+
+/// Builds the path to the first node whose key is not less than `key`.
+fn seek<K, Q, P>(path: &mut Vec<P>, root: Option<P>, key: &Q)
+where
+    P: PathNode<K>,
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    let mut current = root;
+    while let Some(node) = current {
+        path.push(node);
+        current = match node.compare(key) {
+            Ordering::Equal => return,
+            Ordering::Less => node.child(0),
+            Ordering::Greater => node.child(1),
+        };
+    }
+    // the search went right of the last node : the key is after it
+    if matches!(path.last(), Some(n) if n.compare(key) == Ordering::Greater) {
+        step(path, root, 1);
+    }
+}
+
+/// A cursor over an [ABR]{struct.ABR.html}
+///
+/// The cursor points to a node of the tree, or to a "ghost" position past
+/// the last node and before the first one. It keeps the path from the
+/// root to its node, so that moving to a neighbour never searches the tree
+/// again.
+///
+/// # Examples
+/// Basic usage :
+///
+/// ```
+/// use abr::abr::ABR;
+///
+/// let tree: ABR<_, _> = vec![5, 3, 7, 1].into_iter().collect();
+///
+/// // the cursor starts on the first key not less than the given one
+/// let mut cursor = tree.cursor(&4);
+/// assert_eq!(cursor.key(), Some(&5));
+/// cursor.move_prev();
+/// cursor.move_prev();
+/// assert_eq!(cursor.key(), Some(&1));
+/// cursor.move_prev();
+/// assert_eq!(cursor.key(), None);
+/// ```
+#[derive(Debug)]
+pub struct Cursor<'a, K, V, A = ()>
+where
+    A: Augment<K, V>,
+{
+    root: Option<&'a Node<K, V, A>>,
+    path: Vec<&'a Node<K, V, A>>,
+}
+
+impl<'a, K, V, A> Cursor<'a, K, V, A>
+where
+    K: Ord,
+    A: Augment<K, V>,
+{
+    pub(crate) fn new<Q>(tree: &'a ABR<K, V, A>, key: &Q) -> Cursor<'a, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let root = tree.root.as_deref();
+        let mut path = Vec::new();
+        seek(&mut path, root, key);
+        Cursor { root, path }
+    }
+
+    /// Moves the cursor to the next node, or to the first one from the
+    /// ghost position.
+    pub fn move_next(&mut self) {
+        step(&mut self.path, self.root, 1);
+    }
+
+    /// Moves the cursor to the previous node, or to the last one from the
+    /// ghost position.
+    pub fn move_prev(&mut self) {
+        step(&mut self.path, self.root, 0);
+    }
+
+    /// Returns the key of the current node, `None` on the ghost position.
+    pub fn key(&self) -> Option<&'a K> {
+        self.path.last().map(|n| &n.key)
+    }
+
+    /// Returns the value of the current node, `None` on the ghost position.
+    pub fn value(&self) -> Option<&'a V> {
+        self.path.last().map(|n| &n.value)
+    }
+}
+
+/// A cursor over an [ABR]{struct.ABR.html}, allowing to modify the tree
+/// around its position.
+///
+/// Sizes and summaries are updated along the path of each modification.
+///
+/// # Examples
+/// Basic usage :
+///
+/// ```
+/// use abr::abr::ABR;
+///
+/// let mut tree: ABR<_, _> = vec![10, 20, 30].into_iter().collect();
+///
+/// let mut cursor = tree.cursor_mut(&20);
+/// cursor.insert_before(15, ());
+/// cursor.insert_after(25, ());
+/// assert_eq!(cursor.remove_current(), Some((20, ())));
+/// assert_eq!(cursor.key(), Some(&25));
+///
+/// assert!(tree.iter().map(|n| n.key).eq(vec![10, 15, 25, 30]));
+/// ```
+#[derive(Debug)]
+pub struct CursorMut<'a, K, V, A = ()>
+where
+    A: Augment<K, V>,
+{
+    tree: NonNull<ABR<K, V, A>>,
+    path: Vec<NonNull<Node<K, V, A>>>,
+    marker: PhantomData<&'a mut ABR<K, V, A>>,
+}
+
+impl<'a, K, V, A> CursorMut<'a, K, V, A>
+where
+    K: Ord,
+    A: Augment<K, V>,
+{
+    pub(crate) fn new<Q>(tree: &'a mut ABR<K, V, A>, key: &Q) -> CursorMut<'a, K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut cursor = CursorMut {
+            tree: NonNull::from(tree),
+            path: Vec::new(),
+            marker: PhantomData,
+        };
+        let root = cursor.root();
+        seek(&mut cursor.path, root, key);
+        cursor
+    }
+
+    fn root(&mut self) -> Option<NonNull<Node<K, V, A>>> {
+        // SAFETY: the tree is mutably borrowed by the cursor
+        unsafe { (*self.tree.as_ptr()).root.as_deref_mut() }.map(NonNull::from)
+    }
+
+    /// Recomputes the sizes and summaries of the given nodes, deepest last.
+    fn update(nodes: &[NonNull<Node<K, V, A>>]) {
+        for node in nodes.iter().rev() {
+            // SAFETY: the nodes are on a path of the borrowed tree
+            unsafe { (*node.as_ptr()).update() };
+        }
+    }
+
+    /// Moves the cursor to the next node, or to the first one from the
+    /// ghost position.
+    pub fn move_next(&mut self) {
+        let root = self.root();
+        step(&mut self.path, root, 1);
+    }
+
+    /// Moves the cursor to the previous node, or to the last one from the
+    /// ghost position.
+    pub fn move_prev(&mut self) {
+        let root = self.root();
+        step(&mut self.path, root, 0);
+    }
+
+    /// Returns the key of the current node, `None` on the ghost position.
+    pub fn key(&self) -> Option<&K> {
+        // SAFETY: the node is in the borrowed tree
+        self.path.last().map(|n| unsafe { &(*n.as_ptr()).key })
+    }
+
+    /// Returns the value of the current node, `None` on the ghost position.
+    pub fn value(&self) -> Option<&V> {
+        // SAFETY: the node is in the borrowed tree
+        self.path.last().map(|n| unsafe { &(*n.as_ptr()).value })
+    }
+
+    /// Inserts a key and value pair just before the current node, or as
+    /// the last node from the ghost position. The cursor does not move.
+    ///
+    /// # Panics
+    /// The function will panic if the key does not lie strictly between
+    /// the keys of the previous node and the current one.
+    pub fn insert_before(&mut self, key: K, value: V) {
+        self.insert_beside(key, value, 0);
+    }
+
+    /// Inserts a key and value pair just after the current node, or as
+    /// the first node from the ghost position. The cursor does not move.
+    ///
+    /// # Panics
+    /// The function will panic if the key does not lie strictly between
+    /// the keys of the current node and the next one.
+    pub fn insert_after(&mut self, key: K, value: V) {
+        self.insert_beside(key, value, 1);
+    }
+
+    fn insert_beside(&mut self, key: K, value: V, direction: usize) {
+        let side = if direction == 0 {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        };
+        let root = self.root();
+        let mut neighbour = self.path.clone();
+        step(&mut neighbour, root, direction);
+        assert!(
+            !matches!(self.path.last(), Some(n) if n.compare(&key) != side)
+                && !matches!(neighbour.last(), Some(n) if n.compare(&key) != side.reverse()),
+            "inserted key is out of order"
+        );
+
+        // the free link is the child in `direction` of the current node,
+        // or the furthest one in the other direction in that subtree
+        let mut ancestors = self.path.clone();
+        let start = match self.path.last() {
+            Some(node) => node.child(direction),
+            None => root,
+        };
+        let link: *mut OptBoxedNode<K, V, A> = match (self.path.last(), start) {
+            (Some(node), None) => unsafe { &mut (*node.as_ptr()).children[direction] },
+            (None, None) => unsafe { &mut (*self.tree.as_ptr()).root },
+            (_, Some(start)) => {
+                descent(&mut ancestors, Some(start), 1 - direction);
+                let last = ancestors.last().unwrap();
+                unsafe { &mut (*last.as_ptr()).children[1 - direction] }
+            }
+        };
+
+        // SAFETY: the link belongs to the borrowed tree, and is empty
+        unsafe {
+            *link = Some(Box::new(Node::new(key, value)));
+            (*self.tree.as_ptr()).length += 1;
+        }
+        CursorMut::update(&ancestors);
+    }
+
+    /// Removes the current node from the tree, returning its key and value,
+    /// and moves the cursor to the next node. Nothing happens on the ghost
+    /// position.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        let current = *self.path.last()?;
+        // SAFETY: all the nodes and links handled belong to the borrowed
+        // tree ; the removed node is taken off the path before being freed
+        unsafe {
+            let node = &mut *current.as_ptr();
+            (*self.tree.as_ptr()).length -= 1;
+
+            if node.children[0].is_some() && node.children[1].is_some() {
+                // the entry of the next node moves into the current one,
+                // which stays on the path
+                let mut chain = Vec::new();
+                let mut link: *mut OptBoxedNode<K, V, A> = &mut node.children[1];
+                while let Some(next) = (*link).as_mut().filter(|n| n.children[0].is_some()) {
+                    chain.push(NonNull::from(&mut **next));
+                    link = &mut next.children[0];
+                }
+                let mut next = (*link).take().unwrap();
+                *link = next.children[1].take();
+                std::mem::swap(&mut node.key, &mut next.key);
+                std::mem::swap(&mut node.value, &mut next.value);
+                CursorMut::update(&chain);
+                CursorMut::update(&self.path);
+                return Some((next.key, next.value));
+            }
+
+            self.path.pop();
+            let mut direction = 0;
+            let link: *mut OptBoxedNode<K, V, A> = match self.path.last() {
+                None => &mut (*self.tree.as_ptr()).root,
+                Some(parent) => {
+                    let parent = &mut *parent.as_ptr();
+                    if !matches!(parent.children[0].as_deref(), Some(n) if std::ptr::eq(n, node)) {
+                        direction = 1;
+                    }
+                    &mut parent.children[direction]
+                }
+            };
+            let mut removed = (*link).take().unwrap();
+            let has_right = removed.children[1].is_some();
+            *link = removed.children[0]
+                .take()
+                .or_else(|| removed.children[1].take());
+            CursorMut::update(&self.path);
+
+            if has_right {
+                // the next node is the first one of the right subtree
+                descent(&mut self.path, (*link).as_deref_mut().map(NonNull::from), 0);
+            } else if direction == 1 {
+                // the parent is done as well
+                climb(&mut self.path, 1);
+            }
+            Some((removed.key, removed.value))
+        }
+    }
+}
+
+impl<'a, K, V> CursorMut<'a, K, V>
+where
+    K: Ord,
+{
+    /// Returns a mutable reference to the value of the current node,
+    /// `None` on the ghost position.
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        // SAFETY: the node is in the borrowed tree
+        self.path
+            .last()
+            .map(|n| unsafe { &mut (*n.as_ptr()).value })
+    }
+}
+
+#[cfg(test)]
+mod cursor_tests {
+    use super::*;
+    use crate::augment::Sum;
+
+    #[test]
+    fn walk_both_ways() {
+        let tree: ABR<_, _> = vec![50, 30, 70, 20, 40, 60, 80, 35].into_iter().collect();
+        let mut cursor = tree.cursor(&0);
+        let mut forward = Vec::new();
+        while let Some(key) = cursor.key() {
+            forward.push(*key);
+            cursor.move_next();
+        }
+        assert_eq!(forward, vec![20, 30, 35, 40, 50, 60, 70, 80]);
+
+        let mut backward = Vec::new();
+        cursor.move_prev();
+        while let Some(key) = cursor.key() {
+            backward.push(*key);
+            cursor.move_prev();
+        }
+        forward.reverse();
+        assert_eq!(backward, forward);
+
+        assert_eq!(tree.cursor(&36).key(), Some(&40));
+        assert_eq!(tree.cursor(&81).key(), None);
+        assert_eq!(ABR::<u32, ()>::new().cursor(&1).key(), None);
+    }
+
+    #[test]
+    fn modify_around() {
+        let mut tree: ABR<u32, u32, Sum> = ABR::default();
+        for &key in &[50, 30, 70, 20, 40, 60, 80, 35, 45] {
+            tree.insert(key, key);
+        }
+
+        {
+            let mut cursor = tree.cursor_mut(&30);
+            cursor.insert_before(25, 25);
+            cursor.insert_after(32, 32);
+            assert_eq!(cursor.remove_current(), Some((30, 30)));
+            assert_eq!(cursor.key(), Some(&32));
+            cursor.move_next();
+            cursor.move_next();
+            // 40 has two children
+            assert_eq!(cursor.remove_current(), Some((40, 40)));
+            assert_eq!(cursor.key(), Some(&45));
+            // 45 is a right leaf : the next node is 50
+            assert_eq!(cursor.remove_current(), Some((45, 45)));
+            assert_eq!(cursor.key(), Some(&50));
+        }
+        {
+            let mut cursor = tree.cursor_mut(&100);
+            cursor.insert_before(90, 90);
+            cursor.insert_after(10, 10);
+            cursor.move_prev();
+            assert_eq!(cursor.remove_current(), Some((90, 90)));
+            assert_eq!(cursor.key(), None);
+        }
+
+        let keys = vec![10, 20, 25, 32, 35, 50, 60, 70, 80];
+        assert!(tree.iter().map(|n| n.key).eq(keys.iter().cloned()));
+        assert_eq!(tree.length, keys.len());
+        assert_eq!(tree.summary(), keys.iter().sum::<u32>());
+        assert_eq!(tree.root.as_ref().unwrap().size, keys.len());
+        assert_eq!(tree.rank(&50), 5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_out_of_order() {
+        let mut tree: ABR<_, _> = vec![10, 20].into_iter().collect();
+        tree.cursor_mut(&20).insert_before(5, ());
+    }
+
+    #[test]
+    fn value_mut() {
+        let mut tree = ABR::new();
+        tree.insert(1, 'a');
+        tree.insert(2, 'b');
+        let mut cursor = tree.cursor_mut(&2);
+        *cursor.value_mut().unwrap() = 'c';
+        assert_eq!(tree.get(&2), Some(&'c'));
+    }
+}
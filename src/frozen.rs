@@ -0,0 +1,469 @@
+#[cfg(feature = "rayon_adaptive")]
+use rayon_adaptive::prelude::*;
+#[cfg(feature = "rayon_adaptive")]
+use rayon_adaptive::BasicPower;
+use std::borrow::Borrow;
+use std::ops::{Bound, RangeBounds};
+
+/// An immutable binary search tree stored in Eytzinger order
+///
+/// The tree is implicit : node `k` (counting from 1) has children `2k` and
+/// `2k + 1`, so the nodes are laid out in breadth-first order in two arrays,
+/// one for the keys and one for the values. The first levels of the tree
+/// stay in cache, and lookups only need a comparison per level, without
+/// any branch depending on it.
+///
+/// A frozen tree is obtained from an `ABR` with `ABR::freeze`.
+///
+/// # Examples
+/// Basic usage :
+///
+/// ```
+/// use abr::abr::ABR;
+///
+/// let mut tree = ABR::new();
+/// for &(key, value) in &[(10, 'a'), (20, 'b'), (30, 'c')] {
+///     tree.insert(key, value);
+/// }
+/// let frozen = tree.freeze();
+///
+/// assert_eq!(frozen.get(&20), Some(&'b'));
+/// assert_eq!(frozen.floor(&25), Some((&20, &'b')));
+/// assert_eq!(frozen.ceiling(&25), Some((&30, &'c')));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrozenABR<K, V> {
+    keys: Vec<K>,
+    values: Vec<V>,
+}
+
+impl<K, V> Default for FrozenABR<K, V> {
+    fn default() -> Self {
+        FrozenABR {
+            keys: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+}
+
+impl<K, V> FrozenABR<K, V>
+where
+    K: Ord,
+{
+    /// Builds a frozen tree from key and value pairs sorted by
+    /// strictly increasing keys.
+    pub fn from_sorted(entries: Vec<(K, V)>) -> FrozenABR<K, V> {
+        debug_assert!(entries.windows(2).all(|w| w[0].0 < w[1].0));
+        let length = entries.len();
+        let positions = inorder_positions(length);
+
+        let mut slots: Vec<Option<(K, V)>> = (0..length).map(|_| None).collect();
+        for (position, entry) in positions.into_iter().zip(entries) {
+            slots[position - 1] = Some(entry);
+        }
+        let (keys, values) = slots.into_iter().map(Option::unwrap).unzip();
+        FrozenABR { keys, values }
+    }
+
+    /// Returns the number of nodes in the tree.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns `true` if the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Follows the path going right whenever `go_right` holds, down to
+    /// the first missing node, whose position is returned.
+    ///
+    /// The path is encoded in the bits of the position : the deepest node
+    /// where the path went left is `k >> (k.trailing_ones() + 1)`, and the
+    /// deepest one where it went right is `k >> (k.trailing_zeros() + 1)`.
+    fn descend<F>(&self, go_right: F) -> usize
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut position = 1;
+        while position <= self.keys.len() {
+            position = 2 * position + go_right(&self.keys[position - 1]) as usize;
+        }
+        position
+    }
+
+    /// Returns the position of the first key not less than `key`, 0 if none.
+    fn lower_bound<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let position = self.descend(|k| k.borrow() < key);
+        position >> (position.trailing_ones() + 1)
+    }
+
+    /// Returns the position of the first key greater than `key`, 0 if none.
+    fn upper_bound<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let position = self.descend(|k| k.borrow() <= key);
+        position >> (position.trailing_ones() + 1)
+    }
+
+    /// Returns the key and value stored at a position, `None` for 0.
+    fn entry(&self, position: usize) -> Option<(&K, &V)> {
+        match position {
+            0 => None,
+            p => Some((&self.keys[p - 1], &self.values[p - 1])),
+        }
+    }
+
+    /// Returns the value associated to a key, if present.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.entry(self.lower_bound(key))
+            .filter(|(k, _)| (*k).borrow() == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Returns `true` if the specified key is contained in the tree.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Returns the entry with the greatest key less than or equal to `key`.
+    pub fn floor<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let position = self.descend(|k| k.borrow() <= key);
+        self.entry(position >> (position.trailing_zeros() + 1))
+    }
+
+    /// Returns the entry with the smallest key greater than or equal to `key`.
+    pub fn ceiling<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.entry(self.lower_bound(key))
+    }
+
+    /// Returns the position of the smallest key, 0 if the tree is empty.
+    fn first(&self) -> usize {
+        self.descend(|_| false) >> 1
+    }
+
+    /// Returns the number of nodes in the subtree at a position.
+    fn subtree_size(&self, position: usize) -> usize {
+        let length = self.keys.len();
+        let (mut first, mut last, mut size) = (position, position, 0);
+        while first <= length {
+            size += last.min(length) - first + 1;
+            first *= 2;
+            last = 2 * last + 1;
+        }
+        size
+    }
+
+    /// Returns the position of the key of rank `rank` in order, 0 if
+    /// there is none.
+    fn position_of_rank(&self, mut rank: usize) -> usize {
+        let mut position = 1;
+        while position <= self.keys.len() {
+            let left = self.subtree_size(2 * position);
+            if rank == left {
+                return position;
+            }
+            position = if rank < left {
+                2 * position
+            } else {
+                rank -= left + 1;
+                2 * position + 1
+            };
+        }
+        0
+    }
+
+    /// Returns the entry of rank `rank` in the order of the keys.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<_, _> = vec![5, 3, 7, 1].into_iter().collect();
+    /// let frozen = tree.freeze();
+    ///
+    /// assert_eq!(frozen.nth(1), Some((&3, &())));
+    /// assert!(frozen.nth(4).is_none());
+    /// ```
+    pub fn nth(&self, rank: usize) -> Option<(&K, &V)> {
+        self.entry(self.position_of_rank(rank))
+    }
+
+    /// Returns an in-order iterator over the entries of the tree.
+    pub fn iter(&self) -> FrozenIterator<'_, K, V> {
+        FrozenIterator {
+            tree: self,
+            current: self.first(),
+            end: 0,
+        }
+    }
+
+    /// Returns an in-order iterator over the entries whose keys lie in
+    /// the given range.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<_, _> = (0..100).collect();
+    /// let frozen = tree.freeze();
+    ///
+    /// assert!(frozen.range(10..20).map(|(k, _)| *k).eq(10..20));
+    /// assert!(frozen.range(95..).map(|(k, _)| *k).eq(95..100));
+    /// ```
+    pub fn range<Q, R>(&self, range: R) -> FrozenIterator<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(key) => self.lower_bound(key),
+            Bound::Excluded(key) => self.upper_bound(key),
+            Bound::Unbounded => self.first(),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(key) => self.upper_bound(key),
+            Bound::Excluded(key) => self.lower_bound(key),
+            Bound::Unbounded => 0,
+        };
+        // an empty range may end before its start
+        let empty = start == 0 || (end != 0 && self.keys[end - 1] < self.keys[start - 1]);
+        FrozenIterator {
+            tree: self,
+            current: if empty { end } else { start },
+            end,
+        }
+    }
+
+    /// Get a parallel iterator (using rayon_adaptive) from the tree.
+    ///
+    /// The entries are divided by their rank, each part then going from
+    /// successor to successor : they come in the order of the keys.
+    /// ```
+    /// use abr::abr::ABR;
+    /// use rayon_adaptive::prelude::*;
+    ///
+    /// let tree: ABR<_, _> = vec![5, 3, 7, 1, 4, 2, 6].into_iter().collect();
+    /// let frozen = tree.freeze();
+    /// assert_eq!(frozen.par_iter().map(|(k, _)| *k).reduce(|| 0, |a, b| a + b), 28);
+    /// ```
+    #[cfg(feature = "rayon_adaptive")]
+    pub fn par_iter(&self) -> FrozenParallelIterator<'_, K, V> {
+        FrozenParallelIterator {
+            tree: self,
+            start: 0,
+            end: self.len(),
+        }
+    }
+}
+
+/// An in-order iterator over the entries of a [FrozenABR]{struct.FrozenABR.html}.
+///
+/// It goes from successor to successor, without any allocation.
+#[derive(Debug)]
+pub struct FrozenIterator<'a, K, V> {
+    tree: &'a FrozenABR<K, V>,
+    current: usize,
+    end: usize,
+}
+
+impl<'a, K, V> Iterator for FrozenIterator<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current == self.end {
+            return None;
+        }
+        let position = self.current;
+        let length = self.tree.keys.len();
+
+        self.current = if 2 * position < length {
+            // leftmost node of the right subtree
+            let mut next = 2 * position + 1;
+            while 2 * next <= length {
+                next *= 2;
+            }
+            next
+        } else {
+            // first ancestor of which we are in the left subtree
+            position >> (position.trailing_ones() + 1)
+        };
+        Some((
+            &self.tree.keys[position - 1],
+            &self.tree.values[position - 1],
+        ))
+    }
+}
+
+/// A parallel iterator (using rayon_adaptive) for the
+/// [FrozenABR]{struct.FrozenABR.html} structure.
+///
+/// It covers the entries of ranks `start` to `end` (excluded), in order.
+#[cfg(feature = "rayon_adaptive")]
+#[derive(Debug)]
+pub struct FrozenParallelIterator<'a, K, V> {
+    tree: &'a FrozenABR<K, V>,
+    start: usize,
+    end: usize,
+}
+
+#[cfg(feature = "rayon_adaptive")]
+impl<'a, K, V> Divisible for FrozenParallelIterator<'a, K, V> {
+    type Power = BasicPower;
+
+    fn base_length(&self) -> Option<usize> {
+        Some(self.end - self.start)
+    }
+
+    fn divide_at(self, index: usize) -> (Self, Self) {
+        let middle = self.end.min(self.start + index);
+        (
+            FrozenParallelIterator {
+                tree: self.tree,
+                start: self.start,
+                end: middle,
+            },
+            FrozenParallelIterator {
+                tree: self.tree,
+                start: middle,
+                end: self.end,
+            },
+        )
+    }
+}
+
+#[cfg(feature = "rayon_adaptive")]
+impl<'a, K, V> ParallelIterator for FrozenParallelIterator<'a, K, V>
+where
+    K: Ord + Sync,
+    V: Sync,
+{
+    type Item = (&'a K, &'a V);
+
+    type SequentialIterator = FrozenIterator<'a, K, V>;
+
+    fn to_sequential(self) -> Self::SequentialIterator {
+        FrozenIterator {
+            tree: self.tree,
+            current: self.tree.position_of_rank(self.start),
+            end: self.tree.position_of_rank(self.end),
+        }
+    }
+
+    fn extract_iter(&mut self, size: usize) -> Self::SequentialIterator {
+        let middle = self.end.min(self.start + size);
+        let front = FrozenParallelIterator {
+            tree: self.tree,
+            start: self.start,
+            end: middle,
+        };
+        self.start = middle;
+        front.to_sequential()
+    }
+}
+
+/// Returns the positions (counting from 1, children of `k` being `2k` and
+/// `2k + 1`) of the nodes of a complete tree of `length` nodes, in order.
+pub(crate) fn inorder_positions(length: usize) -> Vec<usize> {
+    let mut positions = Vec::with_capacity(length);
+    let mut stack = Vec::new();
+    let mut current = 1;
+    while current <= length || !stack.is_empty() {
+        while current <= length {
+            stack.push(current);
+            current *= 2;
+        }
+        let position = stack.pop().unwrap();
+        positions.push(position);
+        current = 2 * position + 1;
+    }
+    positions
+}
+
+#[cfg(test)]
+mod frozen_tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn random_entries(size: usize) -> BTreeMap<u32, u32> {
+        let mut x: u64 = 11;
+        (0..size)
+            .map(|i| {
+                x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                (((x >> 33) % 10_000) as u32 * 2, i as u32)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lookups() {
+        for &size in &[0, 1, 2, 3, 7, 8, 100, 1000] {
+            let reference = random_entries(size);
+            let frozen = FrozenABR::from_sorted(reference.clone().into_iter().collect());
+            assert_eq!(frozen.len(), reference.len());
+            assert!(frozen.iter().eq(reference.iter()));
+
+            for key in 0..20_002 {
+                assert_eq!(frozen.get(&key), reference.get(&key));
+                assert_eq!(frozen.floor(&key), reference.range(..=key).next_back());
+                assert_eq!(frozen.ceiling(&key), reference.range(key..).next());
+            }
+        }
+    }
+
+    #[test]
+    fn ranks() {
+        for &size in &[0, 1, 2, 3, 7, 8, 100, 1000] {
+            let reference = random_entries(size);
+            let frozen = FrozenABR::from_sorted(reference.clone().into_iter().collect());
+            for (rank, key) in reference.keys().enumerate() {
+                let position = frozen.position_of_rank(rank);
+                assert_eq!(&frozen.keys[position - 1], key);
+            }
+            assert_eq!(frozen.subtree_size(1), frozen.len());
+            assert_eq!(frozen.position_of_rank(frozen.len()), 0);
+        }
+    }
+
+    #[test]
+    fn ranges() {
+        let reference = random_entries(500);
+        let frozen = FrozenABR::from_sorted(reference.clone().into_iter().collect());
+        for &(start, end) in &[(0, 0), (100, 5000), (4001, 4001), (9000, 30_000), (50, 10)] {
+            assert!(frozen
+                .range((Bound::Included(start), Bound::Excluded(end)))
+                .eq(reference.iter().filter(|(k, _)| start <= **k && **k < end)));
+            assert!(frozen
+                .range((Bound::Excluded(start), Bound::Included(end)))
+                .eq(reference.iter().filter(|(k, _)| start < **k && **k <= end)));
+        }
+        assert_eq!(frozen.range(..).count(), reference.len());
+    }
+}
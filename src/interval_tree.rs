@@ -0,0 +1,414 @@
+use crate::abr::ABR;
+use crate::augment::Augment;
+use crate::node::BoxedNode;
+#[cfg(feature = "rayon_adaptive")]
+use crate::node::Node;
+#[cfg(feature = "rayon")]
+use crate::parallel::{join_if, SEQUENTIAL_CUTOFF};
+#[cfg(feature = "rayon_adaptive")]
+use rayon_adaptive::prelude::*;
+use std::fmt::Debug;
+
+pub type IntervalNode<T, V> = BoxedNode<(T, T), V, MaxEnd>;
+
+/// Keeps the largest end point of the intervals of a subtree.
+///
+/// Keys are `(start, end)` intervals, the summary of an empty subtree
+/// is `None`.
+#[derive(Debug)]
+pub struct MaxEnd;
+
+impl<T, V> Augment<(T, T), V> for MaxEnd
+where
+    T: Ord + Clone + Debug,
+{
+    type Summary = Option<T>;
+
+    fn identity() -> Option<T> {
+        None
+    }
+
+    fn summarize(key: &(T, T), _value: &V) -> Option<T> {
+        Some(key.1.clone())
+    }
+
+    fn combine(left: &Option<T>, right: &Option<T>) -> Option<T> {
+        std::cmp::max(left, right).clone()
+    }
+}
+
+/// Returns `true` if the closed intervals `a` and `b` overlap.
+fn overlaps<T: Ord>(a: &(T, T), b: &(T, T)) -> bool {
+    a.0 <= b.1 && b.0 <= a.1
+}
+
+/// An interval tree
+///
+/// Keys are closed `(start, end)` intervals, ordered by start then end.
+/// Each node keeps the largest end point of its subtree, allowing
+/// to skip whole subtrees when looking for overlapping intervals.
+#[derive(Debug)]
+pub struct IntervalTree<T, V>
+where
+    T: Ord + Clone + Debug,
+{
+    pub tree: ABR<(T, T), V, MaxEnd>,
+}
+
+impl<T, V> Default for IntervalTree<T, V>
+where
+    T: Ord + Clone + Debug,
+{
+    fn default() -> Self {
+        IntervalTree::new()
+    }
+}
+
+impl<T, V> IntervalTree<T, V>
+where
+    T: Ord + Clone + Debug,
+{
+    /// Create a new, empty interval tree.
+    pub fn new() -> IntervalTree<T, V> {
+        IntervalTree {
+            tree: ABR::default(),
+        }
+    }
+
+    /// Inserts an interval and its value in the tree
+    ///
+    /// If the exact same interval is already present, its value
+    /// is replaced and returned.
+    ///
+    /// # Panics
+    /// The function will panic if the interval's start is after its end.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::interval_tree::IntervalTree;
+    ///
+    /// let mut windows = IntervalTree::new();
+    ///
+    /// assert!(windows.insert((9, 12), "morning").is_none());
+    /// assert_eq!(windows.insert((9, 12), "meeting"), Some("morning"));
+    /// ```
+    pub fn insert(&mut self, interval: (T, T), value: V) -> Option<V> {
+        assert!(interval.0 <= interval.1, "interval ends before its start");
+        self.tree.insert(interval, value)
+    }
+
+    /// Returns the value associated to an interval, if present.
+    pub fn get(&self, interval: &(T, T)) -> Option<&V> {
+        self.tree.get(interval)
+    }
+
+    /// Removes an interval from the tree, returning its value if present.
+    pub fn remove(&mut self, interval: &(T, T)) -> Option<V> {
+        self.tree.remove(interval)
+    }
+
+    /// Returns the number of intervals in the tree.
+    pub fn len(&self) -> usize {
+        self.tree.length
+    }
+
+    /// Returns `true` if the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Returns an iterator over the intervals overlapping the closed
+    /// interval `query`, in order.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::interval_tree::IntervalTree;
+    ///
+    /// let mut windows = IntervalTree::new();
+    /// windows.insert((1, 3), 'a');
+    /// windows.insert((2, 8), 'b');
+    /// windows.insert((5, 6), 'c');
+    /// windows.insert((9, 10), 'd');
+    ///
+    /// assert!(windows.overlapping((4, 5)).map(|n| n.value).eq(vec!['b', 'c']));
+    /// ```
+    pub fn overlapping(&self, query: (T, T)) -> OverlappingIterator<'_, T, V> {
+        let mut iterator = OverlappingIterator {
+            stack: Vec::new(),
+            query,
+        };
+        iterator.descent(self.tree.root.as_ref());
+        iterator
+    }
+
+    /// Returns an iterator over the intervals containing `point`, in order.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::interval_tree::IntervalTree;
+    ///
+    /// let mut windows = IntervalTree::new();
+    /// windows.insert((1, 3), 'a');
+    /// windows.insert((2, 8), 'b');
+    /// windows.insert((5, 6), 'c');
+    ///
+    /// assert!(windows.containing(3).map(|n| n.value).eq(vec!['a', 'b']));
+    /// assert_eq!(windows.containing(9).count(), 0);
+    /// ```
+    pub fn containing(&self, point: T) -> OverlappingIterator<'_, T, V> {
+        self.overlapping((point.clone(), point))
+    }
+
+    /// Counts in parallel (using rayon) the intervals overlapping the
+    /// closed interval `query`.
+    ///
+    /// Like [overlapping](#method.overlapping), subtrees which cannot
+    /// contain an overlapping interval are skipped, the remaining subtrees
+    /// are counted concurrently.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::interval_tree::IntervalTree;
+    ///
+    /// let mut windows = IntervalTree::new();
+    /// for start in 0..100 {
+    ///     windows.insert((start, start + 10), ());
+    /// }
+    ///
+    /// assert_eq!(windows.par_overlap_count((20, 30)), 21);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_overlap_count(&self, query: (T, T)) -> usize
+    where
+        T: Sync,
+        V: Sync,
+    {
+        overlap_count(self.tree.root.as_ref(), &query)
+    }
+
+    /// Counts in parallel (using rayon_adaptive) the intervals overlapping
+    /// the closed interval `query`.
+    ///
+    /// The intervals starting after the query come last in order : they
+    /// are split off the parallel iterator before counting the others.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::interval_tree::IntervalTree;
+    ///
+    /// let mut windows = IntervalTree::new();
+    /// for start in 0..100 {
+    ///     windows.insert((start, start + 10), ());
+    /// }
+    ///
+    /// assert_eq!(windows.adaptive_par_overlap_count((20, 30)), 21);
+    /// ```
+    #[cfg(feature = "rayon_adaptive")]
+    pub fn adaptive_par_overlap_count(&self, query: (T, T)) -> usize
+    where
+        T: Sync,
+        V: Sync,
+    {
+        let candidates = starting_before(self.tree.root.as_ref(), &query.1);
+        let (candidates, _) = self.tree.adaptive_par_iter().divide_at(candidates);
+        candidates
+            .map(|node| overlaps(&node.key, &query) as usize)
+            .reduce(|| 0, |a, b| a + b)
+    }
+}
+
+/// Counts the intervals of a subtree starting before or at `end`.
+#[cfg(feature = "rayon_adaptive")]
+fn starting_before<T, V>(mut current: Option<&IntervalNode<T, V>>, end: &T) -> usize
+where
+    T: Ord + Clone + Debug,
+{
+    let mut count = 0;
+    while let Some(node) = current {
+        if node.key.0 <= *end {
+            count += 1 + Node::subtree_size(&node.children[0]);
+            current = node.children[1].as_ref();
+        } else {
+            current = node.children[0].as_ref();
+        }
+    }
+    count
+}
+
+/// Counts the intervals of a subtree overlapping `query`, splitting the
+/// work between the two children of large subtrees.
+#[cfg(feature = "rayon")]
+fn overlap_count<T, V>(node: Option<&IntervalNode<T, V>>, query: &(T, T)) -> usize
+where
+    T: Ord + Clone + Debug + Sync,
+    V: Sync,
+{
+    let node = match node {
+        Some(node) => node,
+        None => return 0,
+    };
+    match &node.summary {
+        Some(end) if *end >= query.0 => {}
+        // every interval of this subtree ends before the query
+        _ => return 0,
+    }
+    if node.key.0 > query.1 {
+        // this interval and the right subtree start after the query
+        return overlap_count(node.children[0].as_ref(), query);
+    }
+    let (left, right) = join_if(
+        node.size >= SEQUENTIAL_CUTOFF,
+        || overlap_count(node.children[0].as_ref(), query),
+        || overlap_count(node.children[1].as_ref(), query),
+    );
+    left + right + overlaps(&node.key, query) as usize
+}
+
+/// An iterator over the intervals of an [IntervalTree]{struct.IntervalTree.html}
+/// overlapping a query interval.
+///
+/// Subtrees which cannot contain an overlapping interval are skipped, using
+/// the largest end point stored in each node.
+#[derive(Debug)]
+pub struct OverlappingIterator<'a, T, V>
+where
+    T: Ord + Clone + Debug,
+{
+    stack: Vec<&'a IntervalNode<T, V>>,
+    query: (T, T),
+}
+
+impl<'a, T, V> OverlappingIterator<'a, T, V>
+where
+    T: Ord + Clone + Debug,
+{
+    fn descent(&mut self, mut start: Option<&'a IntervalNode<T, V>>) {
+        while let Some(node) = start {
+            match &node.summary {
+                Some(end) if *end >= self.query.0 => {}
+                // every interval of this subtree ends before the query
+                _ => break,
+            }
+            if node.key.0 <= self.query.1 {
+                self.stack.push(node);
+            }
+            start = node.children[0].as_ref();
+        }
+    }
+}
+
+impl<'a, T, V> Iterator for OverlappingIterator<'a, T, V>
+where
+    T: Ord + Clone + Debug,
+{
+    type Item = &'a IntervalNode<T, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            // nodes are only stacked when starting before the query's end
+            self.descent(node.children[1].as_ref());
+            if overlaps(&node.key, &self.query) {
+                return Some(node);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod interval_tree_tests {
+    use super::*;
+
+    fn random_tree(size: usize) -> IntervalTree<u32, usize> {
+        let mut tree = IntervalTree::new();
+        let mut x: u64 = 7;
+        for i in 0..size {
+            x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            let start = ((x >> 33) % 1000) as u32;
+            let length = ((x >> 17) % 50) as u32;
+            tree.insert((start, start + length), i);
+        }
+        tree
+    }
+
+    #[test]
+    fn overlapping_matches_brute_force() {
+        let mut tree = random_tree(300);
+        let removed: Vec<(u32, u32)> = tree.tree.iter().step_by(3).map(|n| n.key).collect();
+        for interval in &removed {
+            tree.remove(interval);
+        }
+
+        for &query in &[(0, 0), (10, 20), (500, 500), (990, 2000), (300, 700)] {
+            let expected: Vec<(u32, u32)> = tree
+                .tree
+                .iter()
+                .map(|n| n.key)
+                .filter(|key| overlaps(key, &query))
+                .collect();
+            let found: Vec<(u32, u32)> = tree.overlapping(query).map(|n| n.key).collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn containing() {
+        let tree = random_tree(300);
+        for point in (0..1100).step_by(37) {
+            assert!(tree
+                .containing(point)
+                .all(|n| n.key.0 <= point && point <= n.key.1));
+            assert_eq!(
+                tree.containing(point).count(),
+                tree.tree
+                    .iter()
+                    .filter(|n| n.key.0 <= point && point <= n.key.1)
+                    .count()
+            );
+        }
+    }
+
+    #[test]
+    fn empty() {
+        let tree: IntervalTree<u32, ()> = IntervalTree::new();
+        assert_eq!(tree.containing(3).count(), 0);
+        #[cfg(feature = "rayon")]
+        assert_eq!(tree.par_overlap_count((0, 10)), 0);
+        #[cfg(feature = "rayon_adaptive")]
+        assert_eq!(tree.adaptive_par_overlap_count((0, 10)), 0);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_overlap_count() {
+        let tree = random_tree(5000);
+        for &query in &[(0, 0), (10, 20), (500, 500), (990, 2000), (300, 700)] {
+            assert_eq!(
+                tree.par_overlap_count(query),
+                tree.overlapping(query).count()
+            );
+        }
+    }
+
+    #[cfg(feature = "rayon_adaptive")]
+    #[test]
+    fn adaptive_par_overlap_count() {
+        let tree = random_tree(5000);
+        for &query in &[(0, 0), (10, 20), (500, 500), (990, 2000), (300, 700)] {
+            assert_eq!(
+                tree.adaptive_par_overlap_count(query),
+                tree.overlapping(query).count()
+            );
+        }
+    }
+}
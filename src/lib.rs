@@ -7,6 +7,25 @@ pub mod utils;
 
 pub mod abr;
 pub mod abr_iterator;
+pub mod abr_multi;
+#[cfg(feature = "rayon_adaptive")]
 pub mod abr_parallel_iterator;
+#[cfg(feature = "rayon")]
+pub mod abr_rayon_iterator;
+pub mod arena;
+pub mod augment;
+#[cfg(feature = "bench")]
 pub mod bencher;
+pub mod btree;
+pub mod concurrent;
+pub mod cursor;
+pub mod frozen;
+pub mod interval_tree;
 pub mod node;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod persistent;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+pub mod traversal;
+pub mod veb;
@@ -1,61 +1,138 @@
+use crate::augment::Augment;
+use std::borrow::Borrow;
 use std::cmp::{Ord, Ordering};
 use std::fmt::Display;
 use std::fs::File;
 use std::io::{BufWriter, Write};
+use std::ops::{Bound, RangeBounds};
 use std::process::Command;
 
-pub type BoxedNode<K, V> = Box<Node<K, V>>;
-pub type OptBoxedNode<K, V> = Option<BoxedNode<K, V>>;
+pub type BoxedNode<K, V, A = ()> = Box<Node<K, V, A>>;
+pub type OptBoxedNode<K, V, A = ()> = Option<BoxedNode<K, V, A>>;
 
 /// A node in the binary search tree
 ///
 /// A node is composed of a key and a corresponding value. It has
-/// a reference to its children, enabling exploration.
+/// a reference to its children, enabling exploration, and keeps
+/// the size of its subtree and its summary for the augmentation `A`.
 #[derive(Debug)]
-pub struct Node<K, V> {
+pub struct Node<K, V, A = ()>
+where
+    A: Augment<K, V>,
+{
     pub key: K,
     pub value: V,
-    pub children: [OptBoxedNode<K, V>; 2],
+    pub children: [OptBoxedNode<K, V, A>; 2],
+    pub size: usize,
+    pub summary: A::Summary,
+}
+
+impl<K, V, A> Clone for Node<K, V, A>
+where
+    K: Clone,
+    V: Clone,
+    A: Augment<K, V>,
+{
+    fn clone(&self) -> Self {
+        Node {
+            key: self.key.clone(),
+            value: self.value.clone(),
+            children: self.children.clone(),
+            size: self.size,
+            summary: self.summary.clone(),
+        }
+    }
 }
 
-impl<K, V> Node<K, V>
+impl<K, V, A> Node<K, V, A>
 where
     K: Ord,
+    A: Augment<K, V>,
 {
     /// Creates a new node with a key and a value
-    pub fn new(key: K, value: V) -> Node<K, V> {
+    pub fn new(key: K, value: V) -> Node<K, V, A> {
+        let summary = A::summarize(&key, &value);
         Node {
             key,
             value,
             children: [None, None],
+            size: 1,
+            summary,
         }
     }
 
+    /// Builds a balanced subtree from the next `length` entries, which
+    /// must come by strictly increasing keys.
+    pub fn from_sorted<I>(entries: &mut I, length: usize) -> OptBoxedNode<K, V, A>
+    where
+        I: Iterator<Item = (K, V)>,
+    {
+        if length == 0 {
+            return None;
+        }
+        let left = Node::from_sorted(entries, length / 2);
+        let (key, value) = entries.next().unwrap();
+        let right = Node::from_sorted(entries, length - length / 2 - 1);
+        let mut node = Node::new(key, value);
+        node.children = [left, right];
+        node.update();
+        Some(Box::new(node))
+    }
+
+    /// Returns the number of nodes in a subtree.
+    pub fn subtree_size(node: &OptBoxedNode<K, V, A>) -> usize {
+        node.as_ref().map(|n| n.size).unwrap_or(0)
+    }
+
+    /// Recomputes the size and the summary of the node from its children.
+    ///
+    /// This must be called each time the node's value or children change.
+    pub fn update(&mut self) {
+        self.size =
+            1 + Node::subtree_size(&self.children[0]) + Node::subtree_size(&self.children[1]);
+        let own = A::summarize(&self.key, &self.value);
+        let left = self.children[0]
+            .as_ref()
+            .map(|c| A::combine(&c.summary, &own))
+            .unwrap_or(own);
+        self.summary = self.children[1]
+            .as_ref()
+            .map(|c| A::combine(&left, &c.summary))
+            .unwrap_or(left);
+    }
+
     /// Inserts a key/value pair in the node's children
     pub fn insert(&mut self, key: K, mut value: V) -> Option<V> {
         let direction = match &key.cmp(&self.key) {
             Ordering::Equal => {
                 std::mem::swap(&mut value, &mut self.value);
+                self.update();
                 return Some(value);
             }
             Ordering::Greater => 1,
             Ordering::Less => 0,
         };
 
-        if let Some(ref mut child) = self.children[direction] {
+        let result = if let Some(ref mut child) = self.children[direction] {
             child.as_mut().insert(key, value)
         } else {
             self.children[direction] = Some(Box::new(Node::new(key, value)));
             None
-        }
+        };
+        self.update();
+        result
     }
 
     /// Fetches and returns if possible a value from a given key.
     ///
     /// If the key is present in the tree, `Some(value)` is returned.
     /// If not, `None` is returned.
-    pub fn get(&self, key: &K) -> Option<&V> {
-        let direction = match key.cmp(&self.key) {
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let direction = match key.cmp(self.key.borrow()) {
             Ordering::Equal => return Some(&self.value),
             Ordering::Less => 0,
             Ordering::Greater => 1,
@@ -68,12 +145,192 @@ where
         }
     }
 
+    /// Fetches and returns if possible a mutable reference to the value
+    /// of a given key.
+    ///
+    /// If the key is present in the tree, `Some(value)` is returned.
+    /// If not, `None` is returned.
+    ///
+    /// Summaries are not updated : use `modify` if the augmentation
+    /// depends on the values.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let direction = match key.cmp(self.key.borrow()) {
+            Ordering::Equal => return Some(&mut self.value),
+            Ordering::Less => 0,
+            Ordering::Greater => 1,
+        };
+
+        if let Some(ref mut child) = self.children[direction] {
+            child.get_mut(key)
+        } else {
+            None
+        }
+    }
+
+    /// Applies a function to the value of a given key, and updates
+    /// the summaries on the path to it.
+    ///
+    /// Returns `true` if the key was found.
+    pub fn modify<Q, F>(&mut self, key: &Q, f: F) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        F: FnOnce(&mut V),
+    {
+        let direction = match key.cmp(self.key.borrow()) {
+            Ordering::Equal => {
+                f(&mut self.value);
+                self.update();
+                return true;
+            }
+            Ordering::Less => 0,
+            Ordering::Greater => 1,
+        };
+
+        let found = if let Some(ref mut child) = self.children[direction] {
+            child.modify(key, f)
+        } else {
+            false
+        };
+        if found {
+            self.update();
+        }
+        found
+    }
+
+    /// Removes a key from a subtree, returning its value if possible.
+    ///
+    /// If the key is present in the subtree, `Some(value)` is returned
+    /// and the summaries on the path to it are updated.
+    /// If not, `None` is returned.
+    pub fn remove<Q>(node: &mut OptBoxedNode<K, V, A>, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let direction = match node.as_ref().map(|n| key.cmp(n.key.borrow())) {
+            None => return None,
+            Some(Ordering::Equal) => return Node::remove_root(node),
+            Some(Ordering::Less) => 0,
+            Some(Ordering::Greater) => 1,
+        };
+
+        let current = node.as_mut().unwrap();
+        let removed = Node::remove(&mut current.children[direction], key);
+        if removed.is_some() {
+            current.update();
+        }
+        removed
+    }
+
+    /// Removes the root of a subtree, reconnecting its children.
+    fn remove_root(node: &mut OptBoxedNode<K, V, A>) -> Option<V> {
+        node.take().map(|mut to_remove| {
+            *node = match (to_remove.children[0].take(), to_remove.children[1].take()) {
+                (None, right) => right,
+                (left, None) => left,
+                (left, right) => {
+                    // the minimum of the right subtree replaces the removed node
+                    let mut right = right;
+                    let mut min_node = Node::take_min(&mut right);
+                    min_node.children = [left, right];
+                    min_node.update();
+                    Some(min_node)
+                }
+            };
+            to_remove.value
+        })
+    }
+
+    /// Detaches the minimum node from a non-empty subtree.
+    fn take_min(node: &mut OptBoxedNode<K, V, A>) -> BoxedNode<K, V, A> {
+        let current = node.as_mut().expect("take min on empty subtree");
+        if current.children[0].is_some() {
+            let min_node = Node::take_min(&mut current.children[0]);
+            current.update();
+            min_node
+        } else {
+            let mut min_node = node.take().unwrap();
+            *node = min_node.children[1].take();
+            min_node
+        }
+    }
+
+    /// Combines the summaries of all the keys of a subtree lying in
+    /// the given range.
+    ///
+    /// `after_start` (resp. `before_end`) tells that every key of the subtree
+    /// is known to be after the start (resp. before the end) of the range,
+    /// in which case the stored summaries are used directly.
+    pub fn aggregate<Q, R>(
+        node: &OptBoxedNode<K, V, A>,
+        range: &R,
+        after_start: bool,
+        before_end: bool,
+    ) -> A::Summary
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let node = match node {
+            Some(node) => node,
+            None => return A::identity(),
+        };
+        if after_start && before_end {
+            return node.summary.clone();
+        }
+
+        let key = node.key.borrow();
+        let node_after_start = after_start
+            || match range.start_bound() {
+                Bound::Included(start) => key >= start,
+                Bound::Excluded(start) => key > start,
+                Bound::Unbounded => true,
+            };
+        if !node_after_start {
+            return Node::aggregate(&node.children[1], range, after_start, before_end);
+        }
+        let node_before_end = before_end
+            || match range.end_bound() {
+                Bound::Included(end) => key <= end,
+                Bound::Excluded(end) => key < end,
+                Bound::Unbounded => true,
+            };
+        if !node_before_end {
+            return Node::aggregate(&node.children[0], range, after_start, before_end);
+        }
+
+        let left = Node::aggregate(&node.children[0], range, after_start, true);
+        let right = Node::aggregate(&node.children[1], range, true, before_end);
+        A::combine(
+            &A::combine(&left, &A::summarize(&node.key, &node.value)),
+            &right,
+        )
+    }
+
     /// Fetches a node object from a given key, if possible
     ///
     /// If the key is present in the tree, `Some(node)` is returned.
     /// If not, `None` is returned.
-    pub fn get_node<'a>(node: &'a mut OptBoxedNode<K, V>, key: &K) -> &'a mut OptBoxedNode<K, V> {
-        let direction = match key.cmp(&node.as_ref().expect("get node on non present key").key) {
+    pub fn get_node<'a, Q>(
+        node: &'a mut OptBoxedNode<K, V, A>,
+        key: &Q,
+    ) -> &'a mut OptBoxedNode<K, V, A>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let direction = match key.cmp(
+            node.as_ref()
+                .expect("get node on non present key")
+                .key
+                .borrow(),
+        ) {
             Ordering::Equal => return node,
             Ordering::Less => 0,
             Ordering::Greater => 1,
@@ -91,7 +348,7 @@ where
     }
 
     /// Fetches and returns the minimum leaf from a node.
-    pub fn get_min(node: &mut OptBoxedNode<K, V>) -> &mut OptBoxedNode<K, V> {
+    pub fn get_min(node: &mut OptBoxedNode<K, V, A>) -> &mut OptBoxedNode<K, V, A> {
         if node.as_ref().expect("get min on non present key").children[0].is_some() {
             Node::get_min(&mut node.as_mut().expect("get min on non present key").children[0])
         } else {
@@ -125,9 +382,10 @@ where
     }
 }
 
-impl<K, V> Node<K, V>
+impl<K, V, A> Node<K, V, A>
 where
     K: Ord + Display,
+    A: Augment<K, V>,
 {
     /// Exports to a dot graphviz file.
     pub fn to_dot(&self, buf: &mut BufWriter<File>) {
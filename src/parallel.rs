@@ -0,0 +1,503 @@
+use crate::abr::{sorted_run, ABR};
+use crate::augment::Augment;
+use crate::node::{Node, OptBoxedNode};
+
+/// Below this number of keys, a batch or a subtree is handled sequentially.
+pub(crate) const SEQUENTIAL_CUTOFF: usize = 1 << 10;
+
+/// Depth under which subtrees are handled sequentially : enough tasks for
+/// each thread to get about 16 of them.
+fn parallel_depth() -> usize {
+    rayon::current_num_threads()
+        .next_power_of_two()
+        .trailing_zeros() as usize
+        + 4
+}
+
+/// Runs the two operations in parallel (using rayon) if the work is worth
+/// it, sequentially otherwise.
+pub(crate) fn join_if<RA, RB, FA, FB>(parallel: bool, left: FA, right: FB) -> (RA, RB)
+where
+    FA: FnOnce() -> RA + Send,
+    FB: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    if parallel {
+        rayon::join(left, right)
+    } else {
+        (left(), right())
+    }
+}
+
+impl<K, V, A> ABR<K, V, A>
+where
+    K: Ord + Send,
+    V: Send,
+    A: Augment<K, V>,
+    A::Summary: Send,
+{
+    /// Inserts a batch of key and value pairs in the tree, in parallel
+    /// (using rayon).
+    ///
+    /// The batch is sorted, then split by the key of each node : the two
+    /// halves go to the left and right subtrees, which are disjoint and
+    /// updated concurrently. The keys falling in an empty subtree form a
+    /// new balanced subtree. As with `insert`, the last value of a key wins.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let mut tree: ABR<u32, u32> = ABR::from_sorted((0..10).map(|k| (k, 0)).collect());
+    /// tree.par_insert_batch((5..15).map(|k| (k, 1)).collect());
+    /// assert_eq!(tree.length, 15);
+    /// assert_eq!(tree.get(&4), Some(&0));
+    /// assert_eq!(tree.get(&5), Some(&1));
+    /// ```
+    pub fn par_insert_batch(&mut self, batch: Vec<(K, V)>) {
+        let inserted = insert_sorted(&mut self.root, sorted_run(batch));
+        self.length += inserted;
+    }
+
+    /// Removes a batch of keys from the tree, in parallel (using rayon).
+    ///
+    /// The keys are sorted, then split by the key of each node : the
+    /// left and right subtrees are updated concurrently. Keys absent from
+    /// the tree are ignored.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let mut tree: ABR<u32, ()> = (0..10).collect();
+    /// tree.par_remove_batch(vec![8, 2, 42, 2]);
+    /// assert_eq!(tree.length, 8);
+    /// assert!(!tree.contains_key(&2));
+    /// ```
+    pub fn par_remove_batch(&mut self, mut batch: Vec<K>) {
+        batch.sort();
+        batch.dedup();
+        let removed = remove_sorted(&mut self.root, batch);
+        self.length -= removed;
+    }
+
+    /// Drops the tree in the background, freeing the left and right
+    /// subtrees of the big nodes in parallel (using rayon).
+    ///
+    /// The nodes are handed to a rayon task : this returns at once,
+    /// without waiting for them to be freed.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<u32, ()> = ABR::from_sorted((0..100_000).map(|k| (k, ())).collect());
+    /// tree.par_drop();
+    /// ```
+    pub fn par_drop(mut self)
+    where
+        K: 'static,
+        V: 'static,
+        A: 'static,
+    {
+        let root = self.root.take();
+        rayon::spawn(move || drop_subtree(root));
+    }
+}
+
+impl<K, V, A> ABR<K, V, A>
+where
+    K: Ord + Sync,
+    V: Sync,
+    A: Augment<K, V>,
+    A::Summary: Sync,
+{
+    /// Builds a new tree with exactly the same shape, where the value of
+    /// each node is computed from its key and value, in parallel (using
+    /// rayon).
+    ///
+    /// Both children of each node are mapped concurrently, no insertion
+    /// takes place. The new tree has its own augmentation `B`, the summaries
+    /// are computed from the new values on the way back up.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    /// use abr::augment::Sum;
+    ///
+    /// let prices: ABR<&str, u32> = ABR::from_sorted(vec![("apple", 3), ("pear", 5)]);
+    /// let with_tax: ABR<_, _> = prices.par_map_values(|_, price| f64::from(*price) * 1.2);
+    /// assert_eq!(with_tax.get(&"pear"), Some(&6.0));
+    /// let in_cents: ABR<_, _, Sum> = prices.par_map_values(|_, price| price * 100);
+    /// assert_eq!(in_cents.summary(), 800);
+    /// ```
+    pub fn par_map_values<W, B, F>(&self, f: F) -> ABR<K, W, B>
+    where
+        K: Clone + Send,
+        W: Send,
+        B: Augment<K, W>,
+        B::Summary: Send,
+        F: Fn(&K, &V) -> W + Sync,
+    {
+        ABR {
+            root: map_subtree(&self.root, &f),
+            length: self.length,
+        }
+    }
+
+    /// Folds the tree subtree by subtree, in parallel (using rayon).
+    ///
+    /// An empty subtree gives `leaf()`, a node combines the results of its
+    /// left subtree, itself and its right subtree with `combine`. Both
+    /// children are folded concurrently near the root, sequentially
+    /// deeper down.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::abr::ABR;
+    ///
+    /// let tree: ABR<u32, ()> = vec![4, 2, 6, 1, 3, 7, 8].into_iter().collect();
+    /// let height = tree.par_fold_subtrees(|| 0, |left, _, right| 1 + left.max(right));
+    /// assert_eq!(height, 4);
+    /// let sum = tree.par_fold_subtrees(|| 0, |left, node, right| left + node.key + right);
+    /// assert_eq!(sum, 31);
+    /// ```
+    pub fn par_fold_subtrees<R, L, C>(&self, leaf: L, combine: C) -> R
+    where
+        R: Send,
+        L: Fn() -> R + Sync,
+        C: Fn(R, &Node<K, V, A>, R) -> R + Sync,
+    {
+        fold_subtree(&self.root, &leaf, &combine, parallel_depth())
+    }
+}
+
+/// Maps the values of a subtree, keeping its shape.
+fn map_subtree<K, V, A, W, B, F>(node: &OptBoxedNode<K, V, A>, f: &F) -> OptBoxedNode<K, W, B>
+where
+    K: Ord + Clone + Send + Sync,
+    V: Sync,
+    A: Augment<K, V>,
+    A::Summary: Sync,
+    W: Send,
+    B: Augment<K, W>,
+    B::Summary: Send,
+    F: Fn(&K, &V) -> W + Sync,
+{
+    node.as_ref().map(|current| {
+        let [left_child, right_child] = &current.children;
+        let (left, right) = join_if(
+            current.size > SEQUENTIAL_CUTOFF,
+            || map_subtree(left_child, f),
+            || map_subtree(right_child, f),
+        );
+        let mut mapped = Node::new(current.key.clone(), f(&current.key, &current.value));
+        mapped.children = [left, right];
+        mapped.update();
+        Box::new(mapped)
+    })
+}
+
+/// Folds a subtree, its children in parallel for `depth` more levels.
+fn fold_subtree<K, V, A, R, L, C>(
+    node: &OptBoxedNode<K, V, A>,
+    leaf: &L,
+    combine: &C,
+    depth: usize,
+) -> R
+where
+    K: Sync,
+    V: Sync,
+    A: Augment<K, V>,
+    A::Summary: Sync,
+    R: Send,
+    L: Fn() -> R + Sync,
+    C: Fn(R, &Node<K, V, A>, R) -> R + Sync,
+{
+    match node {
+        None => leaf(),
+        Some(current) => {
+            let [left_child, right_child] = &current.children;
+            let depth = depth.saturating_sub(1);
+            let (left, right) = join_if(
+                depth > 0,
+                || fold_subtree(left_child, leaf, combine, depth),
+                || fold_subtree(right_child, leaf, combine, depth),
+            );
+            combine(left, current, right)
+        }
+    }
+}
+
+/// Inserts entries sorted by strictly increasing keys in a subtree,
+/// returning the number of new keys.
+fn insert_sorted<K, V, A>(node: &mut OptBoxedNode<K, V, A>, mut batch: Vec<(K, V)>) -> usize
+where
+    K: Ord + Send,
+    V: Send,
+    A: Augment<K, V>,
+    A::Summary: Send,
+{
+    if batch.is_empty() {
+        return 0;
+    }
+    let current = match node {
+        Some(current) => current,
+        None => {
+            let length = batch.len();
+            *node = Node::from_sorted(&mut batch.into_iter(), length);
+            return length;
+        }
+    };
+
+    let parallel = batch.len() > SEQUENTIAL_CUTOFF;
+    let (right, own) = match batch.binary_search_by(|entry| entry.0.cmp(&current.key)) {
+        Ok(index) => {
+            let right = batch.split_off(index + 1);
+            (right, batch.pop())
+        }
+        Err(index) => (batch.split_off(index), None),
+    };
+    let [left_child, right_child] = &mut current.children;
+    let (left_count, right_count) = join_if(
+        parallel,
+        || insert_sorted(left_child, batch),
+        || insert_sorted(right_child, right),
+    );
+    if let Some((_, value)) = own {
+        current.value = value;
+    }
+    current.update();
+    left_count + right_count
+}
+
+/// Removes strictly increasing keys from a subtree, returning the number
+/// of keys removed.
+fn remove_sorted<K, V, A>(node: &mut OptBoxedNode<K, V, A>, mut batch: Vec<K>) -> usize
+where
+    K: Ord + Send,
+    V: Send,
+    A: Augment<K, V>,
+    A::Summary: Send,
+{
+    let current = match node {
+        Some(current) if !batch.is_empty() => current,
+        _ => return 0,
+    };
+
+    let parallel = batch.len() > SEQUENTIAL_CUTOFF;
+    let (right, own) = match batch.binary_search(&current.key) {
+        Ok(index) => {
+            let right = batch.split_off(index + 1);
+            (right, batch.pop())
+        }
+        Err(index) => (batch.split_off(index), None),
+    };
+    let [left_child, right_child] = &mut current.children;
+    let (left_count, right_count) = join_if(
+        parallel,
+        || remove_sorted(left_child, batch),
+        || remove_sorted(right_child, right),
+    );
+    current.update();
+    match own {
+        Some(key) => {
+            Node::remove(node, &key);
+            left_count + right_count + 1
+        }
+        None => left_count + right_count,
+    }
+}
+
+/// Drops a subtree, its children in separate tasks if it is big enough.
+fn drop_subtree<K, V, A>(node: OptBoxedNode<K, V, A>)
+where
+    K: Send,
+    V: Send,
+    A: Augment<K, V>,
+    A::Summary: Send,
+{
+    if let Some(mut node) = node {
+        if node.size > SEQUENTIAL_CUTOFF {
+            let [left, right] = std::mem::take(&mut node.children);
+            rayon::join(|| drop_subtree(left), || drop_subtree(right));
+        }
+    }
+}
+
+#[cfg(test)]
+mod parallel_tests {
+    use super::*;
+    use crate::augment::{Max, Sum};
+
+    fn random_keys(size: usize, seed: u64) -> Vec<u64> {
+        let mut x = seed;
+        (0..size)
+            .map(|_| {
+                x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                (x >> 33) % 100_000
+            })
+            .collect()
+    }
+
+    fn check_sizes<K, V, A: Augment<K, V>>(node: &OptBoxedNode<K, V, A>) -> usize {
+        node.as_ref().map_or(0, |n| {
+            let size = 1 + check_sizes(&n.children[0]) + check_sizes(&n.children[1]);
+            assert_eq!(n.size, size);
+            size
+        })
+    }
+
+    #[test]
+    fn insert_batch() {
+        let mut tree: ABR<u64, usize> = ABR::new();
+        let mut expected: ABR<u64, usize> = ABR::new();
+        for (round, seed) in [1, 2, 3].iter().enumerate() {
+            let batch: Vec<(u64, usize)> = random_keys(20_000, *seed)
+                .into_iter()
+                .enumerate()
+                .map(|(i, k)| (k, round * 100_000 + i))
+                .collect();
+            for &(key, value) in &batch {
+                expected.insert(key, value);
+            }
+            tree.par_insert_batch(batch);
+            assert_eq!(tree.length, expected.length);
+            assert_eq!(check_sizes(&tree.root), tree.length);
+            assert!(tree
+                .iter()
+                .map(|n| (n.key, n.value))
+                .eq(expected.iter().map(|n| (n.key, n.value))));
+        }
+    }
+
+    fn same_shape<K: PartialEq, V, W>(a: &OptBoxedNode<K, V>, b: &OptBoxedNode<K, W>) -> bool {
+        match (a, b) {
+            (None, None) => true,
+            (Some(a), Some(b)) => {
+                a.key == b.key
+                    && a.size == b.size
+                    && same_shape(&a.children[0], &b.children[0])
+                    && same_shape(&a.children[1], &b.children[1])
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn map_values() {
+        let tree: ABR<u64, ()> = random_keys(20_000, 6).into_iter().collect();
+        let mapped: ABR<_, _> = tree.par_map_values(|key, _| key * 2);
+        assert_eq!(mapped.length, tree.length);
+        assert!(same_shape(&tree.root, &mapped.root));
+        assert!(mapped.iter().all(|n| n.value == n.key * 2));
+        let empty: ABR<u64, u64> = ABR::<u64, ()>::new().par_map_values(|_, _| 0);
+        assert!(empty.is_empty());
+
+        // every summary is computed from the new values
+        let counted: ABR<_, _, Sum> = tree.par_map_values(|_, _| 1usize);
+        assert!(counted.iter().all(|n| n.summary == n.size));
+        let maxima: ABR<_, _, Max> = tree.par_map_values(|key, _| key % 1000);
+        assert_eq!(maxima.summary(), tree.iter().map(|n| n.key % 1000).max());
+    }
+
+    #[test]
+    fn fold_subtrees() {
+        let tree: ABR<u64, ()> = random_keys(20_000, 7).into_iter().collect();
+        let sizes = tree.par_fold_subtrees(|| 0, |left, _, right| left + 1 + right);
+        assert_eq!(sizes, tree.length);
+        let keys = tree.par_fold_subtrees(Vec::new, |mut left, node, right| {
+            left.push(node.key);
+            left.extend(right);
+            left
+        });
+        assert!(keys.into_iter().eq(tree.iter().map(|n| n.key)));
+        assert_eq!(
+            ABR::<u64, ()>::new().par_fold_subtrees(|| 7, |_, _, _| 0),
+            7
+        );
+    }
+
+    #[test]
+    fn drop() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+        struct Counted;
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let keys = random_keys(50_000, 8);
+        let mut tree: ABR<u64, Counted> = ABR::new();
+        for &key in &keys {
+            tree.insert(key, Counted);
+        }
+        let length = tree.length;
+        DROPPED.store(0, Ordering::Relaxed);
+        tree.par_drop();
+        while DROPPED.load(Ordering::Relaxed) < length {
+            std::thread::yield_now();
+        }
+        assert_eq!(DROPPED.load(Ordering::Relaxed), length);
+    }
+
+    #[test]
+    fn drop_in_background() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+        static GATE: Mutex<()> = Mutex::new(());
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+        struct Gated;
+        impl Drop for Gated {
+            fn drop(&mut self) {
+                std::mem::drop(GATE.lock().unwrap());
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let tree: ABR<u64, Gated> = ABR::from_sorted((0..10_000).map(|k| (k, Gated)).collect());
+        let gate = GATE.lock().unwrap();
+        // no node can be freed while the gate is closed
+        tree.par_drop();
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 0);
+        std::mem::drop(gate);
+        while DROPPED.load(Ordering::Relaxed) < 10_000 {
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn remove_batch() {
+        let keys = random_keys(50_000, 4);
+        let mut tree: ABR<u64, ()> = keys.iter().cloned().collect();
+        let mut expected = tree.clone();
+        let batch = random_keys(30_000, 5);
+        for key in &batch {
+            expected.remove(key);
+        }
+        tree.par_remove_batch(batch);
+        assert_eq!(tree.length, expected.length);
+        assert_eq!(check_sizes(&tree.root), tree.length);
+        assert!(tree
+            .iter()
+            .map(|n| n.key)
+            .eq(expected.iter().map(|n| n.key)));
+
+        tree.par_remove_batch(keys);
+        assert!(tree.is_empty());
+        assert!(tree.root.is_none());
+    }
+}
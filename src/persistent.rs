@@ -0,0 +1,620 @@
+use crate::abr_iterator::{pop_next, push_left_path, skip_front, SizedNode};
+#[cfg(feature = "rayon")]
+use rayon::iter::plumbing::{bridge_unindexed, Folder, UnindexedConsumer, UnindexedProducer};
+#[cfg(feature = "rayon")]
+use rayon::iter::IntoParallelIterator;
+#[cfg(feature = "rayon_adaptive")]
+use rayon_adaptive::prelude::*;
+#[cfg(feature = "rayon_adaptive")]
+use rayon_adaptive::BasicPower;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::vec::IntoIter;
+
+pub type ArcNode<K, V> = Arc<PersistentNode<K, V>>;
+pub type OptArcNode<K, V> = Option<ArcNode<K, V>>;
+
+/// A node in a persistent binary search tree
+///
+/// A node is never modified once created : its children are shared
+/// between all the versions of the tree containing them.
+#[derive(Debug)]
+pub struct PersistentNode<K, V> {
+    pub key: K,
+    pub value: V,
+    pub children: [OptArcNode<K, V>; 2],
+    pub size: usize,
+}
+
+impl<K, V> PersistentNode<K, V> {
+    /// Create a new node above the given children.
+    pub fn new(key: K, value: V, children: [OptArcNode<K, V>; 2]) -> ArcNode<K, V> {
+        let size = 1
+            + PersistentNode::subtree_size(&children[0])
+            + PersistentNode::subtree_size(&children[1]);
+        Arc::new(PersistentNode {
+            key,
+            value,
+            children,
+            size,
+        })
+    }
+
+    /// Returns the number of nodes in a subtree.
+    pub fn subtree_size(node: &OptArcNode<K, V>) -> usize {
+        node.as_ref().map_or(0, |n| n.size)
+    }
+}
+
+impl<K, V> SizedNode for ArcNode<K, V> {
+    fn children(&self) -> &[Option<Self>; 2] {
+        &self.children
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl<K, V> PersistentNode<K, V>
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    /// Returns a copy of the node with one of its children replaced.
+    fn with_child(&self, direction: usize, child: OptArcNode<K, V>) -> ArcNode<K, V> {
+        let mut children = self.children.clone();
+        children[direction] = child;
+        PersistentNode::new(self.key.clone(), self.value.clone(), children)
+    }
+
+    /// Inserts a key/value pair in a subtree, copying the path to it.
+    ///
+    /// Returns the root of the new subtree, and whether a node was added.
+    fn insert(node: &OptArcNode<K, V>, key: K, value: V) -> (ArcNode<K, V>, bool) {
+        let node = match node {
+            Some(node) => node,
+            None => return (PersistentNode::new(key, value, [None, None]), true),
+        };
+
+        let direction = match key.cmp(&node.key) {
+            Ordering::Equal => {
+                let replaced = PersistentNode::new(key, value, node.children.clone());
+                return (replaced, false);
+            }
+            Ordering::Less => 0,
+            Ordering::Greater => 1,
+        };
+
+        let (child, added) = PersistentNode::insert(&node.children[direction], key, value);
+        (node.with_child(direction, Some(child)), added)
+    }
+
+    /// Removes a key from a subtree, copying the path to it.
+    ///
+    /// Returns the root of the new subtree if the key was found.
+    fn remove<Q>(node: &OptArcNode<K, V>, key: &Q) -> Option<OptArcNode<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let node = node.as_ref()?;
+
+        let direction = match key.cmp(node.key.borrow()) {
+            Ordering::Equal => {
+                return Some(match &node.children {
+                    [None, right] => right.clone(),
+                    [left, None] => left.clone(),
+                    [left, right] => {
+                        // the minimum of the right subtree replaces the removed node
+                        let (right, min_node) = PersistentNode::remove_min(right.as_ref().unwrap());
+                        Some(PersistentNode::new(
+                            min_node.key.clone(),
+                            min_node.value.clone(),
+                            [left.clone(), right],
+                        ))
+                    }
+                });
+            }
+            Ordering::Less => 0,
+            Ordering::Greater => 1,
+        };
+
+        let child = PersistentNode::remove(&node.children[direction], key)?;
+        Some(Some(node.with_child(direction, child)))
+    }
+
+    /// Removes the minimum node of a subtree, copying the path to it.
+    ///
+    /// Returns the root of the new subtree and the removed node.
+    fn remove_min(node: &ArcNode<K, V>) -> (OptArcNode<K, V>, ArcNode<K, V>) {
+        match &node.children[0] {
+            None => (node.children[1].clone(), node.clone()),
+            Some(left) => {
+                let (left, min_node) = PersistentNode::remove_min(left);
+                (Some(node.with_child(0, left)), min_node)
+            }
+        }
+    }
+}
+
+/// A persistent binary search tree with a key/value system
+///
+/// Insertions and removals do not modify the tree : they return a new
+/// version of it, sharing all its nodes with the previous one except
+/// the ones on the path to the modified key. Cloning a version is
+/// cheap, and every version can be read (and iterated in parallel)
+/// from any thread while new versions are being created.
+///
+/// # Examples
+/// Basic usage :
+///
+/// ```
+/// use abr::persistent::PersistentABR;
+///
+/// let empty = PersistentABR::new();
+/// let first = empty.insert(1, "hello");
+/// let second = first.insert(2, "world");
+///
+/// assert!(empty.is_empty());
+/// assert_eq!(first.length, 1);
+/// assert_eq!(second.get(&2), Some(&"world"));
+/// assert!(first.get(&2).is_none());
+/// ```
+#[derive(Debug)]
+pub struct PersistentABR<K, V> {
+    pub root: OptArcNode<K, V>,
+    pub length: usize,
+}
+
+impl<K, V> Clone for PersistentABR<K, V> {
+    fn clone(&self) -> Self {
+        PersistentABR {
+            root: self.root.clone(),
+            length: self.length,
+        }
+    }
+}
+
+impl<K, V> Default for PersistentABR<K, V> {
+    fn default() -> Self {
+        PersistentABR {
+            root: None,
+            length: 0,
+        }
+    }
+}
+
+impl<K, V> PersistentABR<K, V>
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    /// Create a new, empty persistent tree.
+    pub fn new() -> PersistentABR<K, V> {
+        PersistentABR::default()
+    }
+
+    /// Returns a new version of the tree with the key and value inserted.
+    ///
+    /// If the key was already present, its value is replaced in the
+    /// new version. Only the nodes on the path to the key are copied.
+    pub fn insert(&self, key: K, value: V) -> PersistentABR<K, V> {
+        let (root, added) = PersistentNode::insert(&self.root, key, value);
+        PersistentABR {
+            root: Some(root),
+            length: self.length + added as usize,
+        }
+    }
+
+    /// Returns a new version of the tree without the given key.
+    ///
+    /// If the key is not present, the new version shares all of its
+    /// nodes with this one.
+    ///
+    /// # Examples
+    /// Basic usage :
+    ///
+    /// ```
+    /// use abr::persistent::PersistentABR;
+    ///
+    /// let before: PersistentABR<_, _> = (1..10).fold(PersistentABR::new(), |t, k| t.insert(k, ()));
+    /// let after = before.remove(&7);
+    ///
+    /// assert!(before.contains_key(&7));
+    /// assert!(!after.contains_key(&7));
+    /// assert_eq!(after.length, 8);
+    /// ```
+    pub fn remove<Q>(&self, key: &Q) -> PersistentABR<K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match PersistentNode::remove(&self.root, key) {
+            Some(root) => PersistentABR {
+                root,
+                length: self.length - 1,
+            },
+            None => self.clone(),
+        }
+    }
+
+    /// Returns the value associated to a key, if present.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut current = self.root.as_ref();
+        while let Some(node) = current {
+            let direction = match key.cmp(node.key.borrow()) {
+                Ordering::Equal => return Some(&node.value),
+                Ordering::Less => 0,
+                Ordering::Greater => 1,
+            };
+            current = node.children[direction].as_ref();
+        }
+        None
+    }
+
+    /// Returns `true` if the specified key is contained in the tree.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Returns `true` if the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns an in-order iterator over this version of the tree.
+    ///
+    /// # Examples
+    /// Basic usage:
+    ///
+    /// ```
+    /// use abr::persistent::PersistentABR;
+    ///
+    /// let tree = vec![5, 3, 7, 1, 4, 2, 6]
+    ///     .into_iter()
+    ///     .fold(PersistentABR::new(), |t, k| t.insert(k, ()));
+    /// assert!(tree.iter().map(|n| n.key).eq(1..=7));
+    /// ```
+    pub fn iter<'a>(&'a self) -> PersistentIterator<'a, K, V> {
+        let mut small_nodes = Vec::new();
+        let mut big_nodes = VecDeque::new();
+        if let Some(root) = &self.root {
+            push_left_path(&mut small_nodes, &mut big_nodes, root);
+        }
+        PersistentIterator {
+            small_nodes: small_nodes.into_iter(),
+            big_nodes,
+            remaining: self.length,
+        }
+    }
+
+    /// Get a parallel iterator (using rayon_adaptive) over this version
+    /// of the tree.
+    ///
+    /// A stock rayon iterator is obtained with `par_iter` (or
+    /// `into_par_iter` on a reference to the tree) with the `rayon`
+    /// feature.
+    /// ```
+    /// use abr::persistent::PersistentABR;
+    /// use rayon_adaptive::prelude::*;
+    ///
+    /// let tree = (1..=7).fold(PersistentABR::new(), |t, k| t.insert(k, ()));
+    /// let snapshot = tree.clone();
+    /// let tree = tree.insert(8, ());
+    ///
+    /// let sum = std::thread::spawn(move || {
+    ///     snapshot.adaptive_par_iter().map(|n| n.key).reduce(|| 0, |a, b| a + b)
+    /// });
+    /// assert_eq!(sum.join().unwrap(), 28);
+    /// assert_eq!(tree.adaptive_par_iter().map(|n| n.key).reduce(|| 0, |a, b| a + b), 36);
+    /// ```
+    #[cfg(feature = "rayon_adaptive")]
+    pub fn adaptive_par_iter<'a>(&'a self) -> PersistentParallelIterator<'a, K, V> {
+        PersistentParallelIterator {
+            iterator: self.iter(),
+        }
+    }
+}
+
+type RefNode<'a, K, V> = &'a ArcNode<K, V>;
+
+/// A sequential iterator over a version of a
+/// [PersistentABR]{struct.PersistentABR.html}, in order.
+///
+/// As with `ABRIterator`, at most `remaining` more nodes are yielded.
+#[derive(Debug)]
+pub struct PersistentIterator<'a, K, V> {
+    small_nodes: IntoIter<RefNode<'a, K, V>>,
+    big_nodes: VecDeque<RefNode<'a, K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Clone for PersistentIterator<'a, K, V> {
+    fn clone(&self) -> Self {
+        PersistentIterator {
+            small_nodes: self.small_nodes.clone(),
+            big_nodes: self.big_nodes.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl<'a, K, V> PersistentIterator<'a, K, V> {
+    /// Skips the next `count` nodes, jumping over whole subtrees as
+    /// `ABRIterator::advance` does.
+    pub fn advance(&mut self, count: usize) {
+        let count = count.min(self.remaining);
+        self.remaining -= count;
+        skip_front(&mut self.small_nodes, &mut self.big_nodes, count);
+    }
+
+    /// Splits off the first `index` nodes into a new iterator, this one
+    /// keeping the nodes after them.
+    ///
+    /// # Examples
+    /// ```
+    /// use abr::persistent::PersistentABR;
+    ///
+    /// let tree = (0..100).fold(PersistentABR::new(), |t, k| t.insert(k, ()));
+    /// let mut back = tree.iter();
+    /// let front = back.split_off_front(30);
+    /// assert!(front.map(|n| n.key).eq(0..30));
+    /// assert!(back.map(|n| n.key).eq(30..100));
+    /// ```
+    pub fn split_off_front(&mut self, index: usize) -> PersistentIterator<'a, K, V> {
+        let index = index.min(self.remaining);
+        let mut front = self.clone();
+        front.remaining = index;
+        self.advance(index);
+        front
+    }
+}
+
+impl<'a, K, V> Iterator for PersistentIterator<'a, K, V> {
+    type Item = &'a ArcNode<K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        pop_next(&mut self.small_nodes, &mut self.big_nodes)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for PersistentIterator<'a, K, V> {}
+
+/// A parallel iterator (using rayon_adaptive) over a version of a
+/// [PersistentABR]{struct.PersistentABR.html}.
+///
+/// As `ABRParallelIterator`, it is divided exactly where asked, using the
+/// sizes of the subtrees.
+#[cfg(feature = "rayon_adaptive")]
+pub struct PersistentParallelIterator<'a, K, V> {
+    iterator: PersistentIterator<'a, K, V>,
+}
+
+#[cfg(feature = "rayon_adaptive")]
+impl<'a, K, V> Divisible for PersistentParallelIterator<'a, K, V> {
+    type Power = BasicPower;
+
+    fn base_length(&self) -> Option<usize> {
+        Some(self.iterator.remaining)
+    }
+
+    fn divide_at(mut self, index: usize) -> (Self, Self) {
+        let front = self.iterator.split_off_front(index);
+        (PersistentParallelIterator { iterator: front }, self)
+    }
+}
+
+#[cfg(feature = "rayon_adaptive")]
+impl<'a, K, V> ParallelIterator for PersistentParallelIterator<'a, K, V>
+where
+    K: Send + Sync,
+    V: Send + Sync,
+{
+    type Item = &'a ArcNode<K, V>;
+
+    type SequentialIterator = PersistentIterator<'a, K, V>;
+
+    fn to_sequential(self) -> Self::SequentialIterator {
+        self.iterator
+    }
+
+    fn extract_iter(&mut self, size: usize) -> Self::SequentialIterator {
+        self.iterator.split_off_front(size)
+    }
+}
+
+/// A parallel iterator (using rayon) over a version of a
+/// [PersistentABR]{struct.PersistentABR.html}.
+///
+/// As `ABRRayonIterator`, each split hands exactly half of the remaining
+/// nodes to each part.
+#[cfg(feature = "rayon")]
+#[derive(Debug)]
+pub struct PersistentRayonIterator<'a, K, V> {
+    iterator: PersistentIterator<'a, K, V>,
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, K, V> rayon::iter::ParallelIterator for PersistentRayonIterator<'a, K, V>
+where
+    K: Send + Sync,
+    V: Send + Sync,
+{
+    type Item = &'a ArcNode<K, V>;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        bridge_unindexed(self, consumer)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, K, V> UnindexedProducer for PersistentRayonIterator<'a, K, V>
+where
+    K: Send + Sync,
+    V: Send + Sync,
+{
+    type Item = &'a ArcNode<K, V>;
+
+    fn split(mut self) -> (Self, Option<Self>) {
+        let length = self.iterator.remaining;
+        if length < 2 {
+            return (self, None);
+        }
+        let front = self.iterator.split_off_front(length / 2);
+        (PersistentRayonIterator { iterator: front }, Some(self))
+    }
+
+    fn fold_with<F>(self, folder: F) -> F
+    where
+        F: Folder<Self::Item>,
+    {
+        folder.consume_iter(self.iterator)
+    }
+}
+
+/// Iterates in parallel (using rayon) over the nodes of a version of the
+/// tree.
+///
+/// # Examples
+///
+/// Basic usage :
+/// ```
+/// use abr::persistent::PersistentABR;
+/// use rayon::prelude::*;
+///
+/// let tree = (1..=7).fold(PersistentABR::new(), |t, k| t.insert(k, ()));
+/// let snapshot = tree.clone();
+/// let tree = tree.insert(8, ());
+///
+/// let sum = std::thread::spawn(move || snapshot.par_iter().map(|n| n.key).sum::<u32>());
+/// assert_eq!(sum.join().unwrap(), 28);
+/// assert_eq!(tree.par_iter().map(|n| n.key).sum::<u32>(), 36);
+/// ```
+#[cfg(feature = "rayon")]
+impl<'a, K, V> IntoParallelIterator for &'a PersistentABR<K, V>
+where
+    K: Ord + Clone + Send + Sync,
+    V: Clone + Send + Sync,
+{
+    type Iter = PersistentRayonIterator<'a, K, V>;
+    type Item = &'a ArcNode<K, V>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        PersistentRayonIterator {
+            iterator: self.iter(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod persistent_tests {
+    use super::*;
+
+    fn build(keys: &[u32]) -> PersistentABR<u32, u32> {
+        keys.iter()
+            .fold(PersistentABR::new(), |tree, &k| tree.insert(k, k * 10))
+    }
+
+    #[test]
+    fn versions_are_independent() {
+        let v1 = build(&[5, 3, 8, 1, 4, 7, 9]);
+        let v2 = v1.insert(6, 60).insert(5, 55);
+        let v3 = v2.remove(&5).remove(&3);
+
+        assert!(v1.iter().map(|n| n.key).eq(vec![1, 3, 4, 5, 7, 8, 9]));
+        assert_eq!(v1.get(&5), Some(&50));
+        assert!(v2.iter().map(|n| n.key).eq(vec![1, 3, 4, 5, 6, 7, 8, 9]));
+        assert_eq!(v2.get(&5), Some(&55));
+        assert!(v3.iter().map(|n| n.key).eq(vec![1, 4, 6, 7, 8, 9]));
+        assert_eq!((v1.length, v2.length, v3.length), (7, 8, 6));
+    }
+
+    #[test]
+    fn path_copying_shares_nodes() {
+        let v1 = build(&[5, 3, 8, 1, 4]);
+        let v2 = v1.insert(9, 90);
+
+        let left = |t: &PersistentABR<u32, u32>| t.root.as_ref().unwrap().children[0].clone();
+        assert!(Arc::ptr_eq(&left(&v1).unwrap(), &left(&v2).unwrap()));
+        assert!(!Arc::ptr_eq(
+            v1.root.as_ref().unwrap(),
+            v2.root.as_ref().unwrap()
+        ));
+    }
+
+    #[test]
+    fn remove_missing_keeps_version() {
+        let v1 = build(&[2, 1, 3]);
+        let v2 = v1.remove(&42);
+        assert!(Arc::ptr_eq(
+            v1.root.as_ref().unwrap(),
+            v2.root.as_ref().unwrap()
+        ));
+        assert_eq!(v2.length, 3);
+    }
+
+    #[test]
+    fn snapshots_read_from_threads() {
+        let mut tree = build(&[50, 25, 75]);
+        let snapshot = tree.clone();
+        let reader = std::thread::spawn(move || snapshot.iter().map(|n| n.key).sum::<u32>());
+        for key in 0..100 {
+            tree = tree.insert(key, key);
+        }
+        assert_eq!(reader.join().unwrap(), 150);
+        assert_eq!(tree.length, 100);
+    }
+
+    #[test]
+    fn split_off_front() {
+        let tree = build(&[50, 25, 75, 10, 30, 60, 90, 5, 27, 65]);
+        let keys: Vec<u32> = tree.iter().map(|n| n.key).collect();
+        for index in 0..=keys.len() + 1 {
+            let mut back = tree.iter();
+            let front = back.split_off_front(index);
+            let cut = index.min(keys.len());
+            assert_eq!((front.len(), back.len()), (cut, keys.len() - cut));
+            assert!(front.map(|n| n.key).eq(keys[..cut].iter().cloned()));
+            assert!(back.map(|n| n.key).eq(keys[cut..].iter().cloned()));
+        }
+        assert_eq!(PersistentNode::subtree_size(&tree.root), tree.length);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn rayon_iterator() {
+        use rayon::iter::ParallelIterator;
+
+        let mut x: u64 = 3;
+        let tree = (0..5000).fold(PersistentABR::new(), |tree, _| {
+            x = x.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            tree.insert(x >> 40, ())
+        });
+        let keys: Vec<u64> = tree.into_par_iter().map(|n| n.key).collect();
+        assert!(keys.into_iter().eq(tree.iter().map(|n| n.key)));
+        assert_eq!(tree.into_par_iter().count(), tree.length);
+        assert_eq!(PersistentABR::<u32, ()>::new().into_par_iter().count(), 0);
+    }
+}
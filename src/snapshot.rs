@@ -0,0 +1,230 @@
+use crate::persistent::PersistentABR;
+use arc_swap::ArcSwap;
+use std::borrow::Borrow;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A tree for read-mostly workloads, published as immutable snapshots
+///
+/// Readers `load` the current snapshot, which they can keep and iterate
+/// (sequentially or in parallel) for as long as they like. Writers start
+/// from the current snapshot, apply a whole batch of updates to their own
+/// version, and publish it atomically : readers see either all of a batch,
+/// or nothing.
+///
+/// The current snapshot is an atomically swapped pointer : loading it
+/// never takes a lock, so readers never wait for writers.
+///
+/// Snapshots are versions of a [PersistentABR]{../persistent/struct.PersistentABR.html} :
+/// each update of a batch only copies the path to its key, the unchanged
+/// subtrees are shared with the previous snapshots.
+///
+/// # Examples
+/// Basic usage :
+///
+/// ```
+/// use abr::snapshot::SnapshotABR;
+///
+/// let routes = SnapshotABR::new();
+///
+/// let mut batch = routes.write();
+/// batch.insert("/", "index");
+/// batch.insert("/about", "about");
+/// batch.publish();
+///
+/// let snapshot = routes.load();
+/// assert_eq!(snapshot.get("/about"), Some(&"about"));
+///
+/// let mut batch = routes.write();
+/// batch.remove("/about");
+/// batch.publish();
+///
+/// // the previous snapshot is not affected
+/// assert_eq!(snapshot.get("/about"), Some(&"about"));
+/// assert!(routes.load().get("/about").is_none());
+/// ```
+#[derive(Debug)]
+pub struct SnapshotABR<K, V> {
+    current: ArcSwap<PersistentABR<K, V>>,
+    writer: Mutex<()>,
+}
+
+impl<K, V> Default for SnapshotABR<K, V> {
+    fn default() -> Self {
+        SnapshotABR::from_tree(PersistentABR::default())
+    }
+}
+
+impl<K, V> SnapshotABR<K, V> {
+    /// Create a new, empty snapshot tree.
+    pub fn new() -> SnapshotABR<K, V> {
+        SnapshotABR::default()
+    }
+
+    /// Create a new snapshot tree, whose first snapshot is the given tree.
+    pub fn from_tree(tree: PersistentABR<K, V>) -> SnapshotABR<K, V> {
+        SnapshotABR {
+            current: ArcSwap::from_pointee(tree),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Returns the current snapshot.
+    ///
+    /// The snapshot is immutable, and stays valid after newer ones
+    /// are published. Loading it is lock-free, and only clones the
+    /// reference to its root.
+    pub fn load(&self) -> PersistentABR<K, V> {
+        self.current.load().as_ref().clone()
+    }
+
+    /// Replaces the current snapshot by the given tree.
+    pub fn store(&self, tree: PersistentABR<K, V>) {
+        let _writer = self.writer.lock().unwrap();
+        self.current.store(Arc::new(tree));
+    }
+
+    /// Starts a batch of updates from the current snapshot.
+    ///
+    /// Only one batch can be in progress at a time : this waits for
+    /// the other writers to publish or drop their batches.
+    pub fn write(&self) -> WriteBatch<'_, K, V> {
+        let writer = self.writer.lock().unwrap();
+        let tree = self.load();
+        WriteBatch {
+            snapshots: self,
+            _writer: writer,
+            tree,
+        }
+    }
+}
+
+/// A batch of updates on a [SnapshotABR]{struct.SnapshotABR.html}
+///
+/// The batch keeps its own version of the tree, replaced by each update.
+/// It is made visible to readers by `publish`, dropping the batch discards
+/// the updates.
+pub struct WriteBatch<'a, K, V> {
+    snapshots: &'a SnapshotABR<K, V>,
+    _writer: MutexGuard<'a, ()>,
+    tree: PersistentABR<K, V>,
+}
+
+impl<'a, K, V> WriteBatch<'a, K, V>
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    /// Inserts a key and value pair in the batch's version of the tree,
+    /// returning the previous value of the key if present.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let previous = self.tree.get(&key).cloned();
+        self.tree = self.tree.insert(key, value);
+        previous
+    }
+
+    /// Removes a key from the batch's version of the tree, returning its
+    /// value if present.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let previous = self.tree.get(key).cloned()?;
+        self.tree = self.tree.remove(key);
+        Some(previous)
+    }
+}
+
+impl<'a, K, V> WriteBatch<'a, K, V> {
+    /// Publishes the updated tree as the new current snapshot.
+    pub fn publish(self) {
+        self.snapshots.current.store(Arc::new(self.tree));
+    }
+}
+
+impl<'a, K, V> Deref for WriteBatch<'a, K, V> {
+    type Target = PersistentABR<K, V>;
+
+    fn deref(&self) -> &PersistentABR<K, V> {
+        &self.tree
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn dropped_batch_is_discarded() {
+        let map = SnapshotABR::new();
+        let mut batch = map.write();
+        batch.insert(1, 'a');
+        drop(batch);
+        assert!(map.load().is_empty());
+    }
+
+    #[test]
+    fn batches_share_unchanged_subtrees() {
+        let map = SnapshotABR::new();
+        let mut batch = map.write();
+        for &key in &[50, 25, 75, 10, 30, 60, 90] {
+            assert!(batch.insert(key, key).is_none());
+        }
+        batch.publish();
+        let before = map.load();
+
+        let mut batch = map.write();
+        assert_eq!(batch.insert(90, 0), Some(90));
+        assert_eq!(batch.remove(&60), Some(60));
+        assert_eq!(batch.remove(&60), None);
+        batch.publish();
+        let after = map.load();
+
+        let left = |t: &PersistentABR<u32, u32>| t.root.as_ref().unwrap().children[0].clone();
+        assert!(Arc::ptr_eq(&left(&before).unwrap(), &left(&after).unwrap()));
+        assert!(before
+            .iter()
+            .map(|n| n.key)
+            .eq(vec![10, 25, 30, 50, 60, 75, 90]));
+        assert!(after.iter().map(|n| (n.key, n.value)).eq(vec![
+            (10, 10),
+            (25, 25),
+            (30, 30),
+            (50, 50),
+            (75, 75),
+            (90, 0)
+        ]));
+    }
+
+    #[test]
+    fn readers_see_whole_batches() {
+        let map = Arc::new(SnapshotABR::new());
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let map = map.clone();
+                thread::spawn(move || {
+                    for _ in 0..200 {
+                        let snapshot = map.load();
+                        // batches always insert ten consecutive keys
+                        assert_eq!(snapshot.length % 10, 0);
+                        assert!(snapshot.iter().map(|n| n.key).eq(0..snapshot.length as u32));
+                    }
+                })
+            })
+            .collect();
+
+        for round in 0..50u32 {
+            let mut batch = map.write();
+            for key in round * 10..(round + 1) * 10 {
+                batch.insert(key, ());
+            }
+            batch.publish();
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(map.load().length, 500);
+    }
+}
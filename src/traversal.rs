@@ -0,0 +1,355 @@
+use crate::abr::ABR;
+use crate::augment::Augment;
+use crate::node::BoxedNode;
+#[cfg(feature = "rayon_adaptive")]
+use rayon_adaptive::prelude::*;
+#[cfg(feature = "rayon_adaptive")]
+use rayon_adaptive::BasicPower;
+use std::collections::VecDeque;
+
+pub type RefNode<'a, K, V, A = ()> = &'a BoxedNode<K, V, A>;
+
+/// The order in which a [DepthFirstIterator]{struct.DepthFirstIterator.html}
+/// yields a node with respect to its subtrees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// Each node comes before its left subtree, then its right one.
+    Pre,
+    /// Each node comes after its left subtree, then its right one.
+    Post,
+}
+
+/// A part of the nodes left to traverse, with its depth : a single node,
+/// or a whole subtree.
+#[derive(Debug)]
+enum Piece<'a, K, V, A>
+where
+    A: Augment<K, V>,
+{
+    Node(RefNode<'a, K, V, A>, usize),
+    Subtree(RefNode<'a, K, V, A>, usize),
+}
+
+#[cfg(feature = "rayon_adaptive")]
+impl<'a, K, V, A> Piece<'a, K, V, A>
+where
+    A: Augment<K, V>,
+{
+    fn len(&self) -> usize {
+        match self {
+            Piece::Node(..) => 1,
+            Piece::Subtree(node, _) => node.size,
+        }
+    }
+}
+
+/// A pre-order or post-order iterator over an [ABR]{struct.ABR.html},
+/// yielding each node along with its depth (0 for the root).
+///
+/// The pieces left to traverse are kept on a stack, the next one on top.
+#[derive(Debug)]
+pub struct DepthFirstIterator<'a, K, V, A = ()>
+where
+    A: Augment<K, V>,
+{
+    stack: Vec<Piece<'a, K, V, A>>,
+    order: Order,
+    remaining: usize,
+}
+
+impl<'a, K, V, A> DepthFirstIterator<'a, K, V, A>
+where
+    A: Augment<K, V>,
+{
+    pub fn new(tree: &'a ABR<K, V, A>, order: Order) -> DepthFirstIterator<'a, K, V, A> {
+        DepthFirstIterator {
+            stack: tree
+                .root
+                .iter()
+                .map(|root| Piece::Subtree(root, 0))
+                .collect(),
+            order,
+            remaining: tree.root.as_ref().map_or(0, |root| root.size),
+        }
+    }
+
+    /// Replaces a subtree on top of the stack by its root and children.
+    fn open(&mut self, node: RefNode<'a, K, V, A>, depth: usize) {
+        if self.order == Order::Post {
+            self.stack.push(Piece::Node(node, depth));
+        }
+        for child in node.children.iter().rev().flatten() {
+            self.stack.push(Piece::Subtree(child, depth + 1));
+        }
+        if self.order == Order::Pre {
+            self.stack.push(Piece::Node(node, depth));
+        }
+    }
+
+    /// Splits off the first `index` nodes (or all of them if there are
+    /// fewer) into a new iterator.
+    #[cfg(feature = "rayon_adaptive")]
+    fn split_off_front(&mut self, index: usize) -> DepthFirstIterator<'a, K, V, A> {
+        let index = index.min(self.remaining);
+        let mut front = Vec::new();
+        let mut taken = 0;
+        while taken < index {
+            let piece = self.stack.pop().unwrap();
+            if taken + piece.len() <= index {
+                taken += piece.len();
+                front.push(piece);
+            } else if let Piece::Subtree(node, depth) = piece {
+                self.open(node, depth);
+            }
+        }
+        front.reverse();
+        self.remaining -= index;
+        DepthFirstIterator {
+            stack: front,
+            order: self.order,
+            remaining: index,
+        }
+    }
+}
+
+impl<'a, K, V, A> Iterator for DepthFirstIterator<'a, K, V, A>
+where
+    A: Augment<K, V>,
+{
+    type Item = (RefNode<'a, K, V, A>, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.pop()? {
+                Piece::Node(node, depth) => {
+                    self.remaining -= 1;
+                    return Some((node, depth));
+                }
+                Piece::Subtree(node, depth) => self.open(node, depth),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V, A> ExactSizeIterator for DepthFirstIterator<'a, K, V, A> where A: Augment<K, V> {}
+
+/// A level-order iterator over an [ABR]{struct.ABR.html}, yielding each
+/// node along with its depth (0 for the root).
+///
+/// Nodes of a same level come from left to right.
+#[derive(Debug)]
+pub struct LevelOrderIterator<'a, K, V, A = ()>
+where
+    A: Augment<K, V>,
+{
+    queue: VecDeque<(RefNode<'a, K, V, A>, usize)>,
+}
+
+impl<'a, K, V, A> LevelOrderIterator<'a, K, V, A>
+where
+    A: Augment<K, V>,
+{
+    pub fn new(tree: &'a ABR<K, V, A>) -> LevelOrderIterator<'a, K, V, A> {
+        LevelOrderIterator {
+            queue: tree.root.iter().map(|root| (root, 0)).collect(),
+        }
+    }
+}
+
+impl<'a, K, V, A> Iterator for LevelOrderIterator<'a, K, V, A>
+where
+    A: Augment<K, V>,
+{
+    type Item = (RefNode<'a, K, V, A>, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth) = self.queue.pop_front()?;
+        for child in node.children.iter().flatten() {
+            self.queue.push_back((child, depth + 1));
+        }
+        Some((node, depth))
+    }
+}
+
+/// A parallel (using rayon_adaptive) pre-order or post-order iterator
+/// over an [ABR]{struct.ABR.html}.
+///
+/// The nodes are divided exactly where asked, using the sizes of the
+/// subtrees : whole subtrees are handed to each part.
+#[cfg(feature = "rayon_adaptive")]
+#[derive(Debug)]
+pub struct DepthFirstParallelIterator<'a, K, V, A = ()>
+where
+    A: Augment<K, V>,
+{
+    iterator: DepthFirstIterator<'a, K, V, A>,
+}
+
+#[cfg(feature = "rayon_adaptive")]
+impl<'a, K, V, A> DepthFirstParallelIterator<'a, K, V, A>
+where
+    A: Augment<K, V>,
+{
+    pub fn new(tree: &'a ABR<K, V, A>, order: Order) -> DepthFirstParallelIterator<'a, K, V, A> {
+        DepthFirstParallelIterator {
+            iterator: DepthFirstIterator::new(tree, order),
+        }
+    }
+}
+
+#[cfg(feature = "rayon_adaptive")]
+impl<'a, K, V, A> Divisible for DepthFirstParallelIterator<'a, K, V, A>
+where
+    A: Augment<K, V>,
+{
+    type Power = BasicPower;
+
+    fn base_length(&self) -> Option<usize> {
+        Some(self.iterator.remaining)
+    }
+
+    fn divide_at(mut self, index: usize) -> (Self, Self) {
+        let front = self.iterator.split_off_front(index);
+        (DepthFirstParallelIterator { iterator: front }, self)
+    }
+}
+
+#[cfg(feature = "rayon_adaptive")]
+impl<'a, K, V, A> ParallelIterator for DepthFirstParallelIterator<'a, K, V, A>
+where
+    K: Sync,
+    V: Sync,
+    A: Augment<K, V>,
+    A::Summary: Sync,
+{
+    type Item = (RefNode<'a, K, V, A>, usize);
+
+    type SequentialIterator = DepthFirstIterator<'a, K, V, A>;
+
+    fn to_sequential(self) -> Self::SequentialIterator {
+        self.iterator
+    }
+
+    fn extract_iter(&mut self, size: usize) -> Self::SequentialIterator {
+        self.iterator.split_off_front(size)
+    }
+}
+
+#[cfg(test)]
+mod traversal_tests {
+    use super::*;
+
+    //        4
+    //      /   \
+    //     2     6
+    //    / \     \
+    //   1   3     7
+    fn small_tree() -> ABR<u32, ()> {
+        vec![4, 2, 6, 1, 3, 7].into_iter().collect()
+    }
+
+    #[test]
+    fn orders_and_depths() {
+        let tree = small_tree();
+        let keys = |v: Vec<(RefNode<u32, ()>, usize)>| -> Vec<(u32, usize)> {
+            v.into_iter().map(|(n, d)| (n.key, d)).collect()
+        };
+        assert_eq!(
+            keys(tree.iter_preorder().collect()),
+            vec![(4, 0), (2, 1), (1, 2), (3, 2), (6, 1), (7, 2)]
+        );
+        assert_eq!(
+            keys(tree.iter_postorder().collect()),
+            vec![(1, 2), (3, 2), (2, 1), (7, 2), (6, 1), (4, 0)]
+        );
+        assert_eq!(
+            keys(tree.iter_level_order().collect()),
+            vec![(4, 0), (2, 1), (6, 1), (1, 2), (3, 2), (7, 2)]
+        );
+        assert_eq!(ABR::<u32, ()>::new().iter_postorder().count(), 0);
+    }
+
+    #[cfg(feature = "rayon_adaptive")]
+    #[test]
+    fn split_off_front() {
+        let tree: ABR<u32, ()> = (0..100).map(|i| (i * 37) % 101).collect();
+        for &order in &[Order::Pre, Order::Post] {
+            let whole: Vec<u32> = DepthFirstIterator::new(&tree, order)
+                .map(|(n, _)| n.key)
+                .collect();
+            for &index in &[0, 1, 10, 50, 99, 100, 150] {
+                let mut back = DepthFirstIterator::new(&tree, order);
+                let front = back.split_off_front(index);
+                let index = index.min(100);
+                assert_eq!((front.len(), back.len()), (index, 100 - index));
+                assert!(front
+                    .chain(back)
+                    .map(|(n, _)| n.key)
+                    .eq(whole.iter().cloned()));
+            }
+        }
+    }
+
+    /// A subtree met by a part of a post-order traversal : its depth, its
+    /// size so far, its real size, and the number of its children not yet
+    /// attached to it (they lie in an earlier part).
+    #[cfg(feature = "rayon_adaptive")]
+    type Subtree = (usize, usize, usize, usize);
+
+    /// Pushes a subtree after the ones met before it, attaching its
+    /// complete children found on top of the stack.
+    #[cfg(feature = "rayon_adaptive")]
+    fn push_subtree(stack: &mut Vec<Subtree>, (depth, mut size, expected, mut missing): Subtree) {
+        while missing > 0 {
+            match stack.last() {
+                Some(&(child_depth, child_size, _, 0)) if child_depth == depth + 1 => {
+                    size += child_size;
+                    missing -= 1;
+                    stack.pop();
+                }
+                _ => break,
+            }
+        }
+        if missing == 0 {
+            assert_eq!(size, expected);
+        }
+        stack.push((depth, size, expected, missing));
+    }
+
+    #[cfg(feature = "rayon_adaptive")]
+    #[test]
+    fn postorder_sizes() {
+        use rayon_adaptive::Policy;
+
+        let tree: ABR<u32, ()> = (0..1000).map(|i| (i * 389) % 1009).collect();
+        for policy in vec![
+            Policy::Sequential,
+            Policy::Join(1),
+            Policy::Join(50),
+            Policy::JoinContext(1),
+            Policy::Adaptive(1, 100),
+        ] {
+            let subtrees = tree
+                .par_iter_postorder()
+                .with_policy(policy)
+                .iterator_fold(|nodes| {
+                    let mut subtrees = Vec::new();
+                    for (node, depth) in nodes {
+                        push_subtree(&mut subtrees, (depth, 1, node.size, node.nb_children()));
+                    }
+                    subtrees
+                })
+                .reduce(Vec::new, |mut subtrees, right| {
+                    for subtree in right {
+                        push_subtree(&mut subtrees, subtree);
+                    }
+                    subtrees
+                });
+            assert_eq!(subtrees, vec![(0, 1000, 1000, 0)]);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::arena::{NodeIndex, NIL};
+use crate::frozen::inorder_positions;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+
+/// A node of a [VebABR]{struct.VebABR.html}
+///
+/// Children are designated by their index in the layout, `NIL` if absent.
+#[derive(Debug, Clone)]
+pub struct VebNode<K> {
+    pub key: K,
+    pub children: [NodeIndex; 2],
+}
+
+/// An immutable binary search tree stored in van Emde Boas order
+///
+/// The tree has the shape of a complete tree. It is cut at half its
+/// height : the top half is stored first, followed by each subtree of the
+/// bottom half, each of these being laid out the same way recursively.
+/// Whatever the size of a cache line, a search goes through O(log_B n)
+/// of them, where B is the number of nodes fitting in a line.
+///
+/// Values are kept apart from the nodes, in the same order, so that
+/// searches only touch keys.
+///
+/// A van Emde Boas tree is obtained from an `ABR` with `ABR::freeze_veb`.
+///
+/// # Examples
+/// Basic usage :
+///
+/// ```
+/// use abr::abr::ABR;
+///
+/// let mut tree = ABR::new();
+/// tree.insert("b", 2);
+/// tree.insert("a", 1);
+/// let frozen = tree.freeze_veb();
+///
+/// assert_eq!(frozen.get("a"), Some(&1));
+/// assert_eq!(frozen.get("c"), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct VebABR<K, V> {
+    nodes: Vec<VebNode<K>>,
+    values: Vec<V>,
+}
+
+impl<K, V> Default for VebABR<K, V> {
+    fn default() -> Self {
+        VebABR {
+            nodes: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+}
+
+/// Pushes the positions of the complete subtree of the given height
+/// rooted at `root`, in van Emde Boas order.
+fn veb_order(root: usize, height: u32, length: usize, order: &mut Vec<usize>) {
+    if root > length {
+        return;
+    }
+    if height == 1 {
+        order.push(root);
+        return;
+    }
+    let top = height / 2;
+    let bottom = height - top;
+    veb_order(root, top, length, order);
+    for leaf in 0..1 << top {
+        veb_order((root << top) + leaf, bottom, length, order);
+    }
+}
+
+impl<K, V> VebABR<K, V>
+where
+    K: Ord,
+{
+    /// Builds a van Emde Boas tree from key and value pairs sorted by
+    /// strictly increasing keys.
+    ///
+    /// # Panics
+    /// The function will panic if there are `u32::MAX` entries or more.
+    pub fn from_sorted(entries: Vec<(K, V)>) -> VebABR<K, V> {
+        debug_assert!(entries.windows(2).all(|w| w[0].0 < w[1].0));
+        let length = entries.len();
+        assert!(length < NIL as usize, "too many entries");
+
+        // positions in the complete tree, indices in the layout
+        let height = 0usize.leading_zeros() - length.leading_zeros();
+        let mut order = Vec::with_capacity(length);
+        veb_order(1, height, length, &mut order);
+        let mut indices = vec![NIL; length + 1];
+        for (index, &position) in order.iter().enumerate() {
+            indices[position] = index as NodeIndex;
+        }
+        let index_of = |position: usize| indices.get(position).cloned().unwrap_or(NIL);
+
+        let mut slots: Vec<Option<(K, V)>> = (0..length).map(|_| None).collect();
+        for (position, entry) in inorder_positions(length).into_iter().zip(entries) {
+            slots[indices[position] as usize] = Some(entry);
+        }
+        let (nodes, values) = slots
+            .into_iter()
+            .zip(order)
+            .map(|(slot, position)| {
+                let (key, value) = slot.unwrap();
+                let children = [index_of(2 * position), index_of(2 * position + 1)];
+                (VebNode { key, children }, value)
+            })
+            .unzip();
+        VebABR { nodes, values }
+    }
+
+    /// Returns the number of nodes in the tree.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the value associated to a key, if present.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut current = if self.nodes.is_empty() { NIL } else { 0 };
+        while current != NIL {
+            let node = &self.nodes[current as usize];
+            current = match key.cmp(node.key.borrow()) {
+                Ordering::Equal => return Some(&self.values[current as usize]),
+                Ordering::Less => node.children[0],
+                Ordering::Greater => node.children[1],
+            };
+        }
+        None
+    }
+
+    /// Returns `true` if the specified key is contained in the tree.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Returns an in-order iterator over the entries of the tree.
+    pub fn iter(&self) -> VebIterator<'_, K, V> {
+        let mut iterator = VebIterator {
+            tree: self,
+            stack: Vec::new(),
+        };
+        if !self.nodes.is_empty() {
+            iterator.descent(0);
+        }
+        iterator
+    }
+}
+
+/// An in-order iterator over the entries of a [VebABR]{struct.VebABR.html}.
+#[derive(Debug)]
+pub struct VebIterator<'a, K, V> {
+    tree: &'a VebABR<K, V>,
+    stack: Vec<NodeIndex>,
+}
+
+impl<'a, K, V> VebIterator<'a, K, V> {
+    fn descent(&mut self, mut start: NodeIndex) {
+        while start != NIL {
+            self.stack.push(start);
+            start = self.tree.nodes[start as usize].children[0];
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for VebIterator<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.stack.pop()? as usize;
+        self.descent(self.tree.nodes[index].children[1]);
+        Some((&self.tree.nodes[index].key, &self.tree.values[index]))
+    }
+}
+
+#[cfg(test)]
+mod veb_tests {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        // a perfect tree of height 4 : the top 2 levels, then 4 subtrees
+        let mut order = Vec::new();
+        veb_order(1, 4, 15, &mut order);
+        assert_eq!(
+            order,
+            vec![1, 2, 3, 4, 8, 9, 5, 10, 11, 6, 12, 13, 7, 14, 15]
+        );
+    }
+
+    #[test]
+    fn lookups_and_iteration() {
+        for &size in &[0, 1, 2, 5, 16, 100, 1000] {
+            let entries: Vec<(u32, u32)> = (0..size).map(|i| (i * 3, i)).collect();
+            let tree = VebABR::from_sorted(entries.clone());
+            assert_eq!(tree.len(), size as usize);
+            assert!(tree.iter().map(|(k, v)| (*k, *v)).eq(entries.into_iter()));
+            for key in 0..size * 3 + 2 {
+                let expected = if key % 3 == 0 && key < size * 3 {
+                    Some(key / 3)
+                } else {
+                    None
+                };
+                assert_eq!(tree.get(&key).cloned(), expected);
+            }
+        }
+    }
+}